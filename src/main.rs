@@ -1,17 +1,26 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Result};
 use clap::Parser;
 use landlock::{
     self, path_beneath_rules, Access, AccessFs, BitFlags, RestrictionStatus, Ruleset, RulesetAttr,
-    RulesetCreatedAttr, ABI,
+    RulesetCreated, RulesetCreatedAttr, ABI,
 };
-use log::{error, info, warn, LevelFilter};
+use log::{debug, error, info, warn, LevelFilter};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Parser, Debug)]
 #[command(name = "ai-sandbox-landlock")]
@@ -45,6 +54,13 @@ struct Args {
     #[arg(long, default_value_t = false)]
     require_landlock: bool,
 
+    /// Strict checks: a kept inherited directory FD outside the allowed
+    /// paths, a file the command needs to start that the ruleset denies, or
+    /// a mount point nested under an allowed path is an error (also profile
+    /// `strict: true`).
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
     /// Log level: error, warn, info, debug, trace.
     #[arg(long)]
     log_level: Option<String>,
@@ -57,6 +73,10 @@ struct Args {
     #[arg(long, default_value_t = false)]
     print_config: bool,
 
+    /// Do not redact secret-looking values in --print-config and logs.
+    #[arg(long, default_value_t = false)]
+    show_secrets: bool,
+
     /// Print planned ruleset and exit.
     #[arg(long, default_value_t = false)]
     print_ruleset: bool,
@@ -73,9 +93,108 @@ struct Args {
     #[arg(long)]
     output: Option<PathBuf>,
 
+    /// How to run the command: replace the launcher (exec) or wait for it (supervise).
+    #[arg(long, value_enum)]
+    exec_mode: Option<ExecMode>,
+
+    /// Wall-clock timeout for the command, in seconds (overrides command.limits.timeout).
+    #[arg(long)]
+    timeout: Option<u64>,
+
+    /// Stop the command after this many seconds without output (overrides command.limits.idle_timeout).
+    #[arg(long)]
+    idle_timeout: Option<u64>,
+
+    /// Run the command on a pseudo-terminal (overrides command.tty).
+    #[arg(long, value_enum)]
+    tty: Option<TtyMode>,
+
+    /// Write a JSON summary of the run to this file when the command exits.
+    #[arg(long)]
+    report: Option<PathBuf>,
+
     /// Command to run inside the sandbox (after "--")
     #[arg(last = true)]
     command: Vec<String>,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Action {
+    /// Run a command under ptrace, record its file accesses and print a profile.
+    Learn(LearnArgs),
+    /// Suggest profile rules for the file operations in an strace log.
+    Suggest(SuggestArgs),
+    /// Explain which rules allow or deny access to a path.
+    Explain(ExplainArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct LearnArgs {
+    /// Path depth at which recorded paths are collapsed into directory groups.
+    #[arg(long, default_value_t = 3)]
+    depth: usize,
+
+    /// Name of the emitted profile (defaults to the command's file name).
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Output file path for the YAML (stdout if omitted).
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Also write every recorded access as "kind<TAB>path" lines to this file.
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    /// Command to learn from (after "--")
+    #[arg(last = true, required = true)]
+    command: Vec<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct SuggestArgs {
+    /// Log from `strace -f -e trace=file` (`-y` helps resolve `*at` calls).
+    #[arg(long, value_name = "FILE")]
+    from_strace: PathBuf,
+
+    /// Config file (YAML) holding the profile to extend.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Profile to compare against; without it every operation is suggested.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Directory relative paths in the log resolve against (defaults to the
+    /// profile's command.working_dir).
+    #[arg(long)]
+    cwd: Option<PathBuf>,
+
+    /// Path depth at which suggested paths are collapsed into directory groups.
+    #[arg(long, default_value_t = 3)]
+    depth: usize,
+}
+
+#[derive(clap::Args, Debug)]
+struct ExplainArgs {
+    /// Path to explain.
+    path: PathBuf,
+
+    /// Rights to explain, comma-separated profile names such as
+    /// write_file,remove_file (all rights if omitted).
+    #[arg(long, value_delimiter = ',')]
+    access: Vec<String>,
+
+    /// Config file (YAML).
+    #[arg(long)]
+    config: PathBuf,
+
+    /// Profile whose ruleset is explained.
+    #[arg(long)]
+    profile: String,
 }
 
 fn main() -> Result<()> {
@@ -86,8 +205,27 @@ fn main() -> Result<()> {
         generate_profile_yaml(&args)?;
         return Ok(());
     }
+    match args.action.as_ref() {
+        Some(Action::Learn(learn)) => {
+            init_logger(args.log_level.as_deref(), args.no_color);
+            return learn_profile(learn);
+        }
+        Some(Action::Suggest(suggest)) => {
+            init_logger(args.log_level.as_deref(), args.no_color);
+            return suggest_from_strace(suggest);
+        }
+        Some(Action::Explain(explain)) => {
+            init_logger(args.log_level.as_deref(), args.no_color);
+            return explain_path(explain);
+        }
+        None => {}
+    }
 
     if args.check {
+        println!(
+            "Unprivileged user namespaces permitted: {}",
+            yesno(probe_unprivileged_userns())
+        );
         return match perform_landlock_check() {
             Ok(report) => {
                 println!("{}", report);
@@ -118,6 +256,12 @@ fn main() -> Result<()> {
             .profiles
             .get(profile_name)
             .ok_or_else(|| anyhow!("profile '{}' not found in config", profile_name))?;
+        if let Some(hooks) = profile.hooks.as_ref() {
+            hooks.validate(&cfg)?;
+        }
+        if let Some(limits) = profile.command.limits.as_ref() {
+            limits.kill_signals()?;
+        }
 
         selected_profile = Some(profile.clone());
         if effective_log_level.is_none() {
@@ -147,7 +291,7 @@ fn main() -> Result<()> {
 
     // Initialize logger after computing effective log level
     init_logger(effective_log_level.as_deref(), args.no_color);
-    info!(
+    let start_line = format!(
         "starting ai-sandbox-landlock with root={:?}, read_only={}, check={}, dry_run={}, require_landlock={}",
         effective_root.as_deref().unwrap_or("<none>"),
         effective_read_only,
@@ -155,11 +299,16 @@ fn main() -> Result<()> {
         args.dry_run,
         args.require_landlock
     );
+    info!("{}", redact_unless(&start_line, args.show_secrets));
 
     // Print config if requested
     if args.print_config {
         if let Some(profile) = selected_profile.as_ref() {
-            let yaml = serde_yaml::to_string(profile)?;
+            let yaml = if args.show_secrets {
+                serde_yaml::to_string(profile)?
+            } else {
+                serde_yaml::to_string(&redact_profile(profile))?
+            };
             println!("Selected profile:\n{}", yaml);
         } else {
             println!(
@@ -170,6 +319,43 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    let spec = selected_profile.as_ref().map(|p| &p.command);
+    let mut limits = spec.and_then(|s| s.limits.clone()).unwrap_or_default();
+    if args.timeout.is_some() {
+        limits.timeout = args.timeout;
+    }
+    if args.idle_timeout.is_some() {
+        limits.idle_timeout = args.idle_timeout;
+    }
+    let mut opts = LaunchOptions {
+        exec_mode: args
+            .exec_mode
+            .or_else(|| spec.and_then(|s| s.exec_mode))
+            .unwrap_or_default(),
+        limits,
+        hardening: selected_profile
+            .as_ref()
+            .and_then(|p| p.hardening.clone())
+            .unwrap_or_default(),
+        stdio: OpenedStdio::default(),
+        pty: None,
+        tmpdir: None,
+        pid_init: None,
+        capabilities: None,
+        seccomp: match selected_profile.as_ref().and_then(|p| p.seccomp.as_ref()) {
+            Some(spec) => SeccompFilter::compile(spec)?,
+            None => None,
+        },
+    };
+    let tty = args
+        .tty
+        .or_else(|| spec.and_then(|s| s.tty))
+        .unwrap_or_default();
+    let hooks = selected_profile
+        .as_ref()
+        .and_then(|p| p.hooks.clone())
+        .unwrap_or_default();
+
     // Print ruleset or dry-run without enforcement
     if args.print_ruleset || args.dry_run {
         if let Some(profile) = selected_profile.as_ref() {
@@ -180,6 +366,43 @@ fn main() -> Result<()> {
                 .ok_or_else(|| anyhow!("project root is required (provide --root or set access_roots.projects in profile)"))?;
             print_ruleset_root(root, effective_read_only)?;
         }
+        let plan = plan_ruleset(
+            selected_profile.as_ref(),
+            effective_root.as_deref(),
+            effective_read_only,
+        )?;
+        let expected = match selected_profile.as_ref() {
+            Some(profile) => launcher_mounts(profile)?,
+            None => Vec::new(),
+        };
+        print_nested_mounts(&current_nested_mounts(&plan, &expected)?);
+        if args.dry_run {
+            if !effective_cmd.is_empty() {
+                print_preflight(&preflight_command(&effective_cmd, spec)?, &plan);
+            }
+            print_environment(spec)?;
+            print_limits(&opts.limits)?;
+            print_hardening(&opts.hardening)?;
+            print_stdio(spec.and_then(|s| s.stdio.as_ref()), tty);
+            print_hooks(&hooks, args.show_secrets);
+            if let Some(profile) = selected_profile.as_ref() {
+                let ns = profile.namespaces.clone().unwrap_or_default();
+                print_namespaces(&ns, profile.network.unwrap_or_default(), &profile.mounts)?;
+                if let Some(seccomp) = profile.seccomp.as_ref() {
+                    print_seccomp(seccomp)?;
+                }
+                if let Some(caps) = profile.capabilities.as_ref() {
+                    print_capabilities(caps)?;
+                }
+            }
+            if selected_profile.as_ref().and_then(|p| p.tmpdir) == Some(TmpdirMode::Private) {
+                println!("Tmpdir:");
+                println!(
+                    "  private = {}/ai-sandbox.XXXXXX (TMPDIR, TMP, TEMP)",
+                    private_tmp_base()
+                );
+            }
+        }
         return Ok(());
     }
 
@@ -189,6 +412,23 @@ fn main() -> Result<()> {
         ));
     }
 
+    let capabilities = selected_profile
+        .as_ref()
+        .and_then(|p| p.capabilities.as_ref());
+    // SAFETY: geteuid cannot fail.
+    let euid_root = unsafe { libc::geteuid() } == 0;
+    if let Some(caps) = capabilities {
+        caps.check_root()?;
+        caps.keep_mask()?;
+    } else if euid_root {
+        warn!("running as root without a capabilities section: the command keeps all capabilities (add `capabilities: {{}}` to refuse root)");
+    }
+    if let Some(profile) = selected_profile.as_ref() {
+        check_reduced_dirs(profile)?;
+        let ns = profile.namespaces.clone().unwrap_or_default();
+        ns.validate(&profile.mounts)?;
+    }
+
     // Landlock availability and require behavior
     let ll_available = perform_landlock_check().is_ok();
     if args.require_landlock && !ll_available {
@@ -197,24 +437,180 @@ fn main() -> Result<()> {
     if !ll_available {
         warn!("Landlock not available; proceeding without sandbox.");
     }
+    if ll_available && selected_profile.is_none() && effective_root.is_none() {
+        return Err(anyhow!(
+            "project root is required (provide --root or set access_roots.projects in profile)"
+        ));
+    }
+
+    // Settings exec mode cannot honour are rejected before anything takes
+    // effect (hooks, redirections, namespaces, the ruleset).
+    let pid_namespace = selected_profile
+        .as_ref()
+        .and_then(|p| p.namespaces.as_ref())
+        .and_then(|n| n.pid)
+        .unwrap_or(false);
+    if opts.exec_mode == ExecMode::Exec {
+        if opts.limits.timeout.is_some() || opts.limits.idle_timeout.is_some() {
+            return Err(anyhow!(
+                "timeout and idle_timeout require exec_mode supervise"
+            ));
+        }
+        if !hooks.post.is_empty() {
+            return Err(anyhow!("post hooks require exec_mode supervise"));
+        }
+        if spec
+            .and_then(|s| s.stdio.as_ref())
+            .is_some_and(|s| s.tee.is_some())
+        {
+            return Err(anyhow!("stdio.tee requires exec_mode supervise"));
+        }
+        if tty.wants_pty() {
+            return Err(anyhow!("tty requires exec_mode supervise"));
+        }
+        if args.report.is_some() {
+            return Err(anyhow!("--report requires exec_mode supervise"));
+        }
+        if pid_namespace {
+            return Err(anyhow!("namespaces.pid requires exec_mode supervise"));
+        }
+        // Nothing would be left to remove the directory after exec.
+        if selected_profile.as_ref().and_then(|p| p.tmpdir) == Some(TmpdirMode::Private) {
+            return Err(anyhow!("tmpdir: private requires exec_mode supervise"));
+        }
+    }
+    if tty.wants_pty()
+        && spec.and_then(|s| s.stdio.as_ref()).is_some_and(|s| {
+            [&s.stdin, &s.stdout, &s.stderr]
+                .iter()
+                .any(|t| !matches!(t, None | Some(StdioTarget::Mode(StdioMode::Inherit))))
+        })
+    {
+        return Err(anyhow!(
+            "tty cannot be combined with stdio.stdin/stdout/stderr redirection (stdio.tee works)"
+        ));
+    }
+
+    // Inherited FDs keep their access after restrict_self; drop them first.
+    let allowed_paths = match selected_profile.as_ref() {
+        Some(profile) => profile_paths(profile)?,
+        None => effective_root
+            .iter()
+            .map(|r| normalize_path(r).map(PathBuf::from))
+            .collect::<Result<Vec<_>>>()?,
+    };
+    let keep_fds = selected_profile
+        .as_ref()
+        .map(|p| p.keep_fds.clone())
+        .unwrap_or_default();
+    let strict = args.strict || selected_profile.as_ref().and_then(|p| p.strict) == Some(true);
+    close_inherited_fds(&keep_fds, &allowed_paths, strict)?;
+
+    // Hooks run once the configuration has been validated, but before
+    // restrict_self, so they are not sandboxed.
+    for hook in &hooks.pre {
+        run_hook(hook, "pre", args.config.as_ref(), None, args.show_secrets)?;
+    }
+    let post_hooks = if hooks.post.is_empty() {
+        None
+    } else {
+        Some(PostHooks::spawn(
+            hooks.post,
+            args.config.clone(),
+            args.show_secrets,
+        )?)
+    };
+
+    // Redirection targets may live outside the sandbox's writable roots.
+    if let Some(stdio) = spec.and_then(|s| s.stdio.as_ref()) {
+        opts.stdio = open_stdio(stdio)?;
+    }
+    if tty.wants_pty() {
+        opts.pty = Some(open_pty()?);
+    }
+
+    // Mounts must be in place before the ruleset opens its paths.
+    if let Some(profile) = selected_profile.as_ref() {
+        let ns = profile.namespaces.clone().unwrap_or_default();
+        enter_namespaces(&ns, profile.network.unwrap_or_default(), &profile.mounts)?;
+    }
+    if selected_profile.as_ref().and_then(|p| p.tmpdir) == Some(TmpdirMode::Private) {
+        opts.tmpdir = Some(PrivateTmp::create()?);
+    }
+    // Opened now, as the report file may be outside the writable roots.
+    let mut report = match args.report.as_ref() {
+        Some(path) => Some(RunReport::create(path, &args, &effective_cmd)?),
+        None => None,
+    };
+
+    let mut deferred_ruleset = None;
 
     // Apply Landlock sandbox either from full profile or a simple root restriction (if available).
     if ll_available {
-        if let Some(profile) = selected_profile.as_ref() {
-            setup_landlock_profile(profile)?;
+        let mut plan = plan_ruleset(
+            selected_profile.as_ref(),
+            effective_root.as_deref(),
+            effective_read_only,
+        )?;
+        if let Some(tmp) = opts.tmpdir.as_ref() {
+            plan.rules.push((tmp.path.clone(), plan.handled));
+        }
+        if let Some(report) = report.as_mut() {
+            report.set_rules(&plan);
+        }
+        let expected = match selected_profile.as_ref() {
+            Some(profile) => launcher_mounts(profile)?,
+            None => Vec::new(),
+        };
+        warn_nested_mounts(&current_nested_mounts(&plan, &expected)?, strict)?;
+        let denied = warn_preflight(&preflight_command(&effective_cmd, spec)?, &plan);
+        if denied > 0 && strict {
+            return Err(anyhow!(
+                "preflight: {} file(s) needed to start the command would be denied",
+                denied
+            ));
+        }
+        let mode = if selected_profile.is_some() {
+            "profile"
         } else {
-            let root = effective_root
-                .as_ref()
-                .ok_or_else(|| anyhow!("project root is required (provide --root or set access_roots.projects in profile)"))?;
-            setup_landlock_root(root, effective_read_only)?;
+            "root"
+        };
+        if pid_namespace {
+            // The namespace's init mounts /proc first, which a Landlock
+            // domain may not do; it applies the ruleset right after.
+            deferred_ruleset = Some(create_ruleset(plan)?);
+            if let Some(report) = report.as_mut() {
+                report.restriction_status = Some("applied by the pid namespace init".into());
+            }
+        } else {
+            let status = setup_landlock(plan, mode)?;
+            if let Some(report) = report.as_mut() {
+                report.restriction_status = Some(format!("{:?}", status));
+            }
         }
     }
+    if pid_namespace {
+        opts.pid_init = Some(PidInit {
+            ruleset: deferred_ruleset,
+        });
+    }
+    if let Some(caps) = capabilities {
+        opts.capabilities = Some(CapabilityDrop::prepare(caps)?);
+    }
+
+    if opts.exec_mode == ExecMode::Exec {
+        // Only returns on failure; on success the command replaces this process.
+        return Err(exec_command(&effective_cmd, spec, &opts));
+    }
 
-    let code = run_command(
-        &effective_cmd,
-        selected_profile.as_ref().map(|p| &p.command),
-    )?;
-    std::process::exit(code);
+    let outcome = run_command(&effective_cmd, spec, opts)?;
+    if let Some(post_hooks) = post_hooks {
+        post_hooks.finish(outcome.code);
+    }
+    if let Some(report) = report {
+        report.write(&outcome)?;
+    }
+    std::process::exit(outcome.code);
 }
 
 fn detect_project_root(args: &Args) -> Result<String> {
@@ -306,9 +702,11 @@ fn generate_profile_yaml(args: &Args) -> Result<()> {
             args: vec![],
             working_dir: Some(root.clone()),
             env: None,
+            ..CommandSpec::default()
         },
         log_level: Some("info".to_string()),
         dry_run: Some(false),
+        ..Profile::default()
     };
 
     let mut profiles = HashMap::new();
@@ -346,10 +744,37 @@ fn init_logger(level: Option<&str>, no_color: bool) {
     builder.init();
 }
 
-fn setup_landlock_root(root: &str, read_only: bool) -> Result<()> {
+/// Handled rights and the (path, allowed) rules passed to Landlock.
+struct PlannedRuleset {
+    handled: BitFlags<AccessFs>,
+    rules: Vec<(PathBuf, BitFlags<AccessFs>)>,
+}
+
+impl PlannedRuleset {
+    /// Rights Landlock grants on `path`: the union over all rules placed on
+    /// the path or one of its ancestors. Both sides are resolved through
+    /// symlinks, as Landlock works on the real hierarchy.
+    fn allowed_for(&self, path: &std::path::Path) -> BitFlags<AccessFs> {
+        let real = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut allowed = BitFlags::<AccessFs>::empty();
+        for (rule_path, access) in &self.rules {
+            let rule_real = fs::canonicalize(rule_path).unwrap_or_else(|_| rule_path.clone());
+            if real.starts_with(&rule_real) {
+                allowed.insert(*access);
+            }
+        }
+        allowed
+    }
+
+    /// Handled rights from `need` that no rule grants on `path`.
+    fn denied_for(&self, path: &std::path::Path, need: BitFlags<AccessFs>) -> BitFlags<AccessFs> {
+        need & self.handled & !self.allowed_for(path)
+    }
+}
+
+fn plan_ruleset_root(root: &str, read_only: bool) -> Result<PlannedRuleset> {
     let _abi = ABI::V1;
     let normalized = normalize_path(root)?;
-    let paths = vec![normalized];
 
     // Allowed per-root permissions
     let allowed = if read_only {
@@ -360,43 +785,99 @@ fn setup_landlock_root(root: &str, read_only: bool) -> Result<()> {
     };
 
     // Handled accesses are whatever we intend to restrict
-    let handled = allowed;
-
-    let created = Ruleset::default().handle_access(handled)?.create()?;
-
-    let created = created.add_rules(path_beneath_rules(&paths, allowed))?;
-    let status: RestrictionStatus = created.restrict_self()?;
-    info!("Landlock applied (root mode): status={:?}", status);
-    Ok(())
+    Ok(PlannedRuleset {
+        handled: allowed,
+        rules: vec![(PathBuf::from(normalized), allowed)],
+    })
 }
 
-fn setup_landlock_profile(profile: &Profile) -> Result<()> {
-    // Collect union of all rights we will handle
-    let mut handled: BitFlags<AccessFs> = BitFlags::empty();
-
-    // Include global control_access into handled rights
-    handled.insert(access_from_control(&profile.control_access));
+/// Rights to create entries (files, directories, symlinks, device nodes)
+/// and, on ABI >= 2, to link or rename them across directories. The
+/// permission model has no names for them, so they are only handled with
+/// `protect`: otherwise new hooks or symlinks could still be planted in a
+/// read-only `.git`. Groups that may write get them as well.
+fn creation_access() -> BitFlags<AccessFs> {
+    let mut set = AccessFs::MakeChar
+        | AccessFs::MakeDir
+        | AccessFs::MakeReg
+        | AccessFs::MakeSock
+        | AccessFs::MakeFifo
+        | AccessFs::MakeBlock
+        | AccessFs::MakeSym;
+    if supported_access().contains(AccessFs::Refer) {
+        set.insert(AccessFs::Refer);
+    }
+    set
+}
 
-    // Union handled accesses from all groups
-    for (_group_name, group) in profile.access_roots.iter() {
-        let allowed = access_from_permissions(&group.permissions);
-        handled.insert(allowed);
+/// Rights a group grants: its permissions, plus creation with `protect`
+/// when it may write.
+fn group_access(profile: &Profile, group: &AccessRootGroup) -> BitFlags<AccessFs> {
+    let allowed = access_from_permissions(&group.permissions);
+    if !profile.protect.is_empty() && allowed.contains(AccessFs::WriteFile) {
+        allowed | creation_access()
+    } else {
+        allowed
     }
+}
 
-    let mut created = Ruleset::default().handle_access(handled)?.create()?;
+/// Union of control_access, all groups' rights and, with `protect`, the
+/// creation rights.
+fn handled_access(profile: &Profile) -> BitFlags<AccessFs> {
+    let mut handled = access_from_control(&profile.control_access);
+    for group in profile.access_roots.values() {
+        handled.insert(group_access(profile, group));
+    }
+    if !profile.protect.is_empty() {
+        handled.insert(creation_access());
+    }
+    handled
+}
 
+fn plan_ruleset_profile(profile: &Profile) -> Result<PlannedRuleset> {
+    let handled = handled_access(profile);
+    let protected = plan_protections(profile)?;
+    let mut rules = Vec::new();
     for (_group_name, group) in profile.access_roots.iter() {
-        let allowed = access_from_permissions(&group.permissions);
+        let allowed = group_access(profile, group);
         let mut norm_paths: Vec<String> = Vec::with_capacity(group.paths.len());
         for p in &group.paths {
             norm_paths.push(normalize_path(p)?);
         }
-        created = created.add_rules(path_beneath_rules(&norm_paths, allowed))?;
+        rules.extend(group_rules(&norm_paths, allowed, &protected));
+    }
+    Ok(PlannedRuleset { handled, rules })
+}
+
+/// Profile mode when a profile is selected, otherwise root mode.
+fn plan_ruleset(
+    profile: Option<&Profile>,
+    root: Option<&str>,
+    read_only: bool,
+) -> Result<PlannedRuleset> {
+    match profile {
+        Some(profile) => plan_ruleset_profile(profile),
+        None => {
+            let root = root.ok_or_else(|| {
+                anyhow!("project root is required (provide --root or set access_roots.projects in profile)")
+            })?;
+            plan_ruleset_root(root, read_only)
+        }
     }
-    let status: RestrictionStatus = created.restrict_self()?;
-    warn!("Applied Landlock; ensure no broad FDs were open before restrict_self.");
-    info!("Landlock applied (profile mode): status={:?}", status);
-    Ok(())
+}
+
+fn create_ruleset(plan: PlannedRuleset) -> Result<RulesetCreated> {
+    let mut created = Ruleset::default().handle_access(plan.handled)?.create()?;
+    for (path, access) in plan.rules {
+        created = created.add_rules(path_beneath_rules([path], access))?;
+    }
+    Ok(created)
+}
+
+fn setup_landlock(plan: PlannedRuleset, mode: &str) -> Result<RestrictionStatus> {
+    let status: RestrictionStatus = create_ruleset(plan)?.restrict_self()?;
+    info!("Landlock applied ({} mode): status={:?}", mode, status);
+    Ok(status)
 }
 
 fn print_ruleset_root(root: &str, read_only: bool) -> Result<()> {
@@ -433,11 +914,7 @@ fn print_ruleset_root(root: &str, read_only: bool) -> Result<()> {
 }
 
 fn print_ruleset_profile(profile: &Profile) -> Result<()> {
-    let mut handled: BitFlags<AccessFs> = BitFlags::empty();
-    handled.insert(access_from_control(&profile.control_access));
-    for (_group_name, group) in profile.access_roots.iter() {
-        handled.insert(access_from_permissions(&group.permissions));
-    }
+    let handled = handled_access(profile);
     let handled_names = access_names(handled);
     let handled_ignored = unsupported_names(handled);
     println!("Ruleset (profile mode):");
@@ -447,7 +924,7 @@ fn print_ruleset_profile(profile: &Profile) -> Result<()> {
     }
     println!("  groups:");
     for (group_name, group) in profile.access_roots.iter() {
-        let allowed = access_from_permissions(&group.permissions);
+        let allowed = group_access(profile, group);
         let names = access_names(allowed);
         let ignored = unsupported_names(allowed);
         println!("    - {}:", group_name);
@@ -460,33 +937,120 @@ fn print_ruleset_profile(profile: &Profile) -> Result<()> {
             println!("        - {}", normalize_path(p)?);
         }
     }
+    let protected = plan_protections(profile)?;
+    if !profile.protect.is_empty() {
+        let kinds: Vec<&str> = profile.protect.iter().map(|k| k.name()).collect();
+        println!("  protect = {:?}", kinds);
+        println!("  protections:");
+        for t in &protected {
+            let state = if t.allowed.is_empty() {
+                "inaccessible"
+            } else {
+                "read-only"
+            };
+            println!("    - {}: {} ({})", t.kind.name(), t.path.display(), state);
+        }
+        let reduced = reduced_dirs(profile, &protected)?;
+        if !reduced.is_empty() {
+            println!("  reduced (warning: these directories lose the rights below, so entries cannot be created or written directly inside):");
+            for (dir, lost) in &reduced {
+                println!("    - {}: {:?}", dir.display(), access_names(*lost));
+            }
+            if !profile.allow_reduced_dirs.unwrap_or(false) {
+                println!("  allow_reduced_dirs is not set: running this profile is refused");
+            }
+        }
+    }
     Ok(())
 }
 
-fn access_names(set: BitFlags<AccessFs>) -> Vec<&'static str> {
-    let mut v = Vec::new();
-    if set.contains(AccessFs::ReadFile) {
-        v.push("ReadFile");
-    }
-    if set.contains(AccessFs::ReadDir) {
-        v.push("ReadDir");
-    }
-    if set.contains(AccessFs::Execute) {
-        v.push("Execute");
-    }
-    if set.contains(AccessFs::WriteFile) {
-        v.push("WriteFile");
+/// `protect` may only take rights from directories leading to a protected
+/// entry with `allow_reduced_dirs`; each reduction is then logged.
+fn check_reduced_dirs(profile: &Profile) -> Result<()> {
+    let reduced = reduced_dirs(profile, &plan_protections(profile)?)?;
+    if reduced.is_empty() {
+        return Ok(());
     }
-    if set.contains(AccessFs::RemoveFile) {
-        v.push("RemoveFile");
+    let list: Vec<String> = reduced
+        .iter()
+        .map(|(dir, lost)| format!("{} loses {:?}", dir.display(), access_names(*lost)))
+        .collect();
+    if !profile.allow_reduced_dirs.unwrap_or(false) {
+        return Err(anyhow!(
+            "protect takes rights from directories leading to protected entries ({}); set allow_reduced_dirs: true to accept this",
+            list.join("; ")
+        ));
     }
-    if set.contains(AccessFs::RemoveDir) {
-        v.push("RemoveDir");
+    for line in &list {
+        warn!("protect: {}", line);
     }
-    if set.contains(AccessFs::Truncate) {
-        v.push("Truncate");
+    Ok(())
+}
+
+/// Directories between an access root and a protected entry, with the
+/// group rights they lose: Landlock cannot carve the entry out of a rule on
+/// its ancestors, so they only keep what the entry keeps (see `group_rules`).
+fn reduced_dirs(
+    profile: &Profile,
+    protected: &[ProtectedPath],
+) -> Result<Vec<(PathBuf, BitFlags<AccessFs>)>> {
+    let mut reduced: Vec<(PathBuf, BitFlags<AccessFs>)> = Vec::new();
+    for group in profile.access_roots.values() {
+        let allowed = group_access(profile, group);
+        let paths = group
+            .paths
+            .iter()
+            .map(|p| normalize_path(p))
+            .collect::<Result<Vec<_>>>()?;
+        let rules = group_rules(&paths, allowed, protected);
+        for root in paths.iter().map(PathBuf::from) {
+            for t in protected.iter().filter(|t| t.path.starts_with(&root)) {
+                for dir in t.path.ancestors().skip(1) {
+                    if !dir.starts_with(&root) {
+                        break;
+                    }
+                    let access = rules
+                        .iter()
+                        .find(|(p, _)| p == dir)
+                        .map(|(_, a)| *a)
+                        .unwrap_or_default();
+                    let lost = allowed & !access;
+                    if !lost.is_empty() && !reduced.iter().any(|(d, _)| d == dir) {
+                        reduced.push((dir.to_path_buf(), lost));
+                    }
+                }
+            }
+        }
     }
-    v
+    reduced.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(reduced)
+}
+
+/// Display names of the rights, in display order.
+const ACCESS_NAMES: &[(AccessFs, &str)] = &[
+    (AccessFs::ReadFile, "ReadFile"),
+    (AccessFs::ReadDir, "ReadDir"),
+    (AccessFs::Execute, "Execute"),
+    (AccessFs::WriteFile, "WriteFile"),
+    (AccessFs::RemoveFile, "RemoveFile"),
+    (AccessFs::RemoveDir, "RemoveDir"),
+    (AccessFs::Truncate, "Truncate"),
+    (AccessFs::MakeReg, "MakeReg"),
+    (AccessFs::MakeDir, "MakeDir"),
+    (AccessFs::MakeSym, "MakeSym"),
+    (AccessFs::MakeChar, "MakeChar"),
+    (AccessFs::MakeBlock, "MakeBlock"),
+    (AccessFs::MakeSock, "MakeSock"),
+    (AccessFs::MakeFifo, "MakeFifo"),
+    (AccessFs::Refer, "Refer"),
+];
+
+fn access_names(set: BitFlags<AccessFs>) -> Vec<&'static str> {
+    ACCESS_NAMES
+        .iter()
+        .filter(|(a, _)| set.contains(*a))
+        .map(|(_, n)| *n)
+        .collect()
 }
 
 fn supported_access() -> BitFlags<AccessFs> {
@@ -508,30 +1072,7 @@ fn supported_access() -> BitFlags<AccessFs> {
 }
 
 fn unsupported_names(requested: BitFlags<AccessFs>) -> Vec<&'static str> {
-    let sup = supported_access();
-    let mut v = Vec::new();
-    if requested.contains(AccessFs::ReadFile) && !sup.contains(AccessFs::ReadFile) {
-        v.push("ReadFile");
-    }
-    if requested.contains(AccessFs::ReadDir) && !sup.contains(AccessFs::ReadDir) {
-        v.push("ReadDir");
-    }
-    if requested.contains(AccessFs::Execute) && !sup.contains(AccessFs::Execute) {
-        v.push("Execute");
-    }
-    if requested.contains(AccessFs::WriteFile) && !sup.contains(AccessFs::WriteFile) {
-        v.push("WriteFile");
-    }
-    if requested.contains(AccessFs::RemoveFile) && !sup.contains(AccessFs::RemoveFile) {
-        v.push("RemoveFile");
-    }
-    if requested.contains(AccessFs::RemoveDir) && !sup.contains(AccessFs::RemoveDir) {
-        v.push("RemoveDir");
-    }
-    if requested.contains(AccessFs::Truncate) && !sup.contains(AccessFs::Truncate) {
-        v.push("Truncate");
-    }
-    v
+    access_names(requested & !supported_access())
 }
 
 // ---------------- Tests ----------------
@@ -626,106 +1167,5322 @@ mod tests {
                 args: vec![],
                 working_dir: None,
                 env: None,
+                ..CommandSpec::default()
             },
             log_level: Some("info".to_string()),
             dry_run: Some(true),
+            ..Profile::default()
         };
         let r = print_ruleset_profile(&profile);
         assert!(r.is_ok());
     }
-}
-
-fn run_command(cmd: &[String], spec: Option<&CommandSpec>) -> Result<i32> {
-    let (bin, args) = cmd
-        .split_first()
-        .ok_or_else(|| anyhow!("command vector is empty"))?;
 
-    let mut cmdp = Command::new(bin);
-    cmdp.args(args);
-    if let Some(spec) = spec {
-        if let Some(wd) = spec.working_dir.as_ref() {
-            let cwd = normalize_path(wd)?;
-            if !std::path::Path::new(&cwd).is_dir() {
-                return Err(anyhow!("working_dir does not exist or is not a directory: {}", cwd));
-            }
-            cmdp.current_dir(cwd);
-        }
-        if let Some(envs) = spec.env.as_ref() {
-            // Normalize env values that use ~/ expansion for better UX
-            let mut norm_envs: HashMap<String, String> = HashMap::with_capacity(envs.len());
-            for (k, v) in envs {
-                let nv = if v.starts_with("~/") {
-                    normalize_path(v)?
-                } else {
-                    v.clone()
-                };
-                norm_envs.insert(k.clone(), nv);
-            }
-            cmdp.envs(norm_envs);
-        }
+    #[test]
+    fn test_exec_mode_parses_from_command() {
+        let spec: CommandSpec =
+            serde_yaml::from_str("binary: /bin/true\nexec_mode: exec\n").unwrap();
+        assert_eq!(spec.exec_mode, Some(ExecMode::Exec));
+        assert_eq!(ExecMode::default(), ExecMode::Supervise);
     }
 
-    let status = cmdp.status()?;
-
-    if let Some(code) = status.code() {
-        Ok(code)
-    } else {
-        // Process terminated by signal; map to generic non-zero code.
-        error!("process terminated by signal");
-        Ok(1)
+    #[test]
+    fn test_glob_match_patterns() {
+        assert!(glob_match("AWS_*", "AWS_SECRET_ACCESS_KEY"));
+        assert!(glob_match("*_TOKEN", "GITHUB_TOKEN"));
+        assert!(glob_match("LC_?", "LC_X"));
+        assert!(!glob_match("AWS_*", "PATH"));
+        assert!(glob_match("*", ""));
     }
-}
 
-// ---------------- Landlock check ----------------
-
-fn perform_landlock_check() -> Result<String> {
-    // 1) Kernel version check (>= 5.13)
-    let osrelease = std::fs::read_to_string("/proc/sys/kernel/osrelease")
-        .unwrap_or_else(|_| String::from("unknown"));
-    let version_ok = parse_kernel_version_ge(&osrelease, 5, 13);
+    #[test]
+    fn test_resolve_env_policies() {
+        let inherited = || {
+            vec![
+                ("PATH".to_string(), "/usr/bin".to_string()),
+                ("GITHUB_TOKEN".to_string(), "ghp_x".to_string()),
+                ("SSH_AUTH_SOCK".to_string(), "/run/agent".to_string()),
+                ("LANG".to_string(), "C".to_string()),
+            ]
+            .into_iter()
+        };
 
-    // 2) LSM list contains 'landlock'
-    let lsm_list = std::fs::read_to_string("/sys/kernel/security/lsm")
-        .unwrap_or_else(|_| String::from("<unavailable>"));
-    let lsm_ok = lsm_list.contains("landlock");
+        let env = resolve_env(None, inherited()).unwrap();
+        assert!(env.vars.contains_key("PATH"));
+        assert!(!env.vars.contains_key("GITHUB_TOKEN"));
+        assert!(!env.vars.contains_key("SSH_AUTH_SOCK"));
 
-    // 3) Optional: try to build minimal ruleset without applying
-    // We only build a report string; defer actual syscall-based check to later.
+        let spec = CommandSpec {
+            env_policy: Some(EnvPolicy::Allowlist),
+            env_passthrough: vec!["PATH".to_string(), "SSH_*".to_string()],
+            env: Some(HashMap::from([("EXTRA".to_string(), "1".to_string())])),
+            ..CommandSpec::default()
+        };
+        let env = resolve_env(Some(&spec), inherited()).unwrap();
+        let keys: Vec<&str> = env.vars.keys().map(|k| k.as_str()).collect();
+        assert_eq!(keys, vec!["EXTRA", "PATH", "SSH_AUTH_SOCK"]);
 
-    let mut report = String::new();
-    report.push_str(&format!("Kernel osrelease: {}\n", osrelease.trim()));
-    report.push_str(&format!("Kernel version >= 5.13: {}\n", yesno(version_ok)));
-    report.push_str(&format!("LSM list: {}\n", lsm_list.trim()));
-    report.push_str(&format!("Landlock listed in LSM: {}\n", yesno(lsm_ok)));
+        let spec = CommandSpec {
+            env_policy: Some(EnvPolicy::Clear),
+            ..CommandSpec::default()
+        };
+        assert!(resolve_env(Some(&spec), inherited())
+            .unwrap()
+            .vars
+            .is_empty());
 
-    if version_ok && lsm_ok {
-        report.push_str("Result: Landlock appears available.\n");
-        Ok(report)
-    } else {
-        Err(anyhow!(
-            "Landlock not available: version_ok={} lsm_ok={}",
-            version_ok,
-            lsm_ok
-        ))
+        let spec = CommandSpec {
+            env_deny: vec!["LANG".to_string()],
+            ..CommandSpec::default()
+        };
+        let env = resolve_env(Some(&spec), inherited()).unwrap();
+        assert!(!env.vars.contains_key("LANG"));
     }
-}
 
-fn yesno(b: bool) -> &'static str {
-    if b {
-        "yes"
-    } else {
-        "no"
+    #[test]
+    fn test_redact_text_hides_tokens() {
+        let line = "run --token=ghp_abcdefghijklmnop1234 --name demo";
+        assert_eq!(redact_text(line), "run --token=<redacted> --name demo");
+        assert_eq!(
+            redact_text("/home/user/dev/project"),
+            "/home/user/dev/project"
+        );
+        assert!(looks_like_secret("a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8"));
+        assert!(!looks_like_secret("libstdc++.so.6"));
     }
-}
 
-fn parse_kernel_version_ge(osrelease: &str, want_major: u32, want_minor: u32) -> bool {
-    // osrelease like "6.8.0-26-generic"
-    let parts: Vec<&str> = osrelease.split('.').collect();
-    if parts.len() < 2 {
-        return false;
+    #[test]
+    fn test_redact_profile_env_values() {
+        let profile = Profile {
+            command: CommandSpec {
+                binary: "code".to_string(),
+                env: Some(HashMap::from([
+                    ("OPENAI_API_KEY".to_string(), "plain".to_string()),
+                    ("HOME".to_string(), "~/.ai-sandbox/home".to_string()),
+                    (
+                        "CUSTOM".to_string(),
+                        "sk-abcdefghijklmnopqrstuv".to_string(),
+                    ),
+                ])),
+                ..CommandSpec::default()
+            },
+            ..Profile::default()
+        };
+        let redacted = redact_profile(&profile);
+        let env = redacted.command.env.unwrap();
+        assert_eq!(env["OPENAI_API_KEY"], REDACTED);
+        assert_eq!(env["HOME"], "~/.ai-sandbox/home");
+        assert_eq!(env["CUSTOM"], REDACTED);
     }
-    let major = parts[0].parse::<u32>().unwrap_or(0);
-    let minor = parts[1].parse::<u32>().unwrap_or(0);
+
+    #[test]
+    fn test_parse_signal_names() {
+        assert_eq!(parse_signal("TERM").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("sigkill").unwrap(), libc::SIGKILL);
+        assert_eq!(parse_signal("2").unwrap(), libc::SIGINT);
+        assert!(parse_signal("BOGUS").is_err());
+        assert!(parse_signal("0").is_err());
+        assert!(parse_signal("-9").is_err());
+        assert!(parse_signal(&(libc::SIGRTMAX() + 1).to_string()).is_err());
+        assert_eq!(
+            parse_signal(&libc::SIGRTMAX().to_string()).unwrap(),
+            libc::SIGRTMAX()
+        );
+        assert_eq!(
+            Limits::default().kill_signals().unwrap(),
+            vec![libc::SIGTERM, libc::SIGKILL]
+        );
+        let term_only = Limits {
+            kill_signals: vec!["TERM".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            term_only.kill_signals().unwrap(),
+            vec![libc::SIGTERM, libc::SIGKILL]
+        );
+        let explicit = Limits {
+            kill_signals: vec!["INT".to_string(), "9".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            explicit.kill_signals().unwrap(),
+            vec![libc::SIGINT, libc::SIGKILL]
+        );
+    }
+
+    #[test]
+    fn test_rlimits_parse_and_validate() {
+        let limits: Limits =
+            serde_yaml::from_str("nofile: 256\ncore: {soft: 0, hard: 0}\n").unwrap();
+        assert_eq!(limits.nofile, Some(RlimitSpec::Value(256)));
+        assert_eq!(limits.rlimits().unwrap().len(), 2);
+
+        let bad = Limits {
+            stack: Some(RlimitSpec::Range { soft: 10, hard: 5 }),
+            ..Limits::default()
+        };
+        let err = bad.rlimits().unwrap_err().to_string();
+        assert!(err.contains("limits.stack"));
+    }
+
+    #[test]
+    fn test_format_utc() {
+        let t = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        assert_eq!(format_utc(t), "2023-11-14T22:13:20.250Z");
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let leap = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(format_utc(leap), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_learned_groups_collapse_by_depth() {
+        let access = |kind, path: &str, dir| LearnedAccess {
+            kind,
+            path: PathBuf::from(path),
+            dir,
+        };
+        let accesses = [
+            access(LearnKind::Execute, "/usr/bin/git", false),
+            access(LearnKind::Read, "/etc/gitconfig", false),
+            access(LearnKind::Read, "/home/u/proj/src/main.rs", false),
+            access(LearnKind::ReadDir, "/home/u/proj/src", true),
+            access(LearnKind::Create, "/home/u/proj/target/out", false),
+            access(LearnKind::Rename, "/home/u/.cache/tool/tmp", true),
+            access(LearnKind::Read, "/home/u/proj/deep/er/file", false),
+        ];
+        let groups = learned_groups(accesses.iter().map(|a| (a, a.rights())), 3);
+        assert_eq!(groups.len(), 3, "{:?}", groups.keys());
+        assert_eq!(groups["system"].paths, vec!["/etc", "/usr"]);
+        let system = access_from_permissions(&groups["system"].permissions);
+        assert_eq!(system, AccessFs::ReadFile | AccessFs::Execute);
+        assert_eq!(groups["proj"].paths, vec!["/home/u/proj"]);
+        let proj = access_from_permissions(&groups["proj"].permissions);
+        assert_eq!(
+            proj,
+            AccessFs::ReadFile | AccessFs::ReadDir | AccessFs::WriteFile
+        );
+        assert_eq!(groups["cache"].paths, vec!["/home/u/.cache"]);
+        let cache = access_from_permissions(&groups["cache"].permissions);
+        assert_eq!(cache, BitFlags::from(AccessFs::RemoveDir));
+    }
+
+    #[test]
+    fn test_parse_strace_log() {
+        let log = r#"100 12:00:00.000001 execve("/usr/bin/make", ["make"], 0x7ffc /* 3 vars */) = 0
+100 chdir("/home/u/proj") = 0
+100 openat(AT_FDCWD, "src/../Makefile", O_RDONLY|O_CLOEXEC) = 3
+100 openat(AT_FDCWD, "gone.mk", O_RDONLY) = -1 ENOENT (No such file or directory)
+[pid   101] openat(AT_FDCWD, "/tmp/a b\303\251", O_WRONLY|O_CREAT|O_TRUNC, 0666 <unfinished ...>
+100 --- SIGCHLD {si_signo=SIGCHLD} ---
+[pid   101] <... openat resumed>) = -1 EACCES (Permission denied)
+101 unlinkat(5</home/u/proj/build>, "x.o", AT_REMOVEDIR) = 0
+101 renameat2(AT_FDCWD, "/tmp/f", 6, "g", RENAME_NOREPLACE) = 0
+101 openat(AT_FDCWD, "rel", O_RDONLY) = 3
+101 +++ exited with 0 +++
+"#;
+        let (ops, unresolved) = parse_strace(log, None);
+        assert_eq!(unresolved, 2);
+        assert_eq!(ops.len(), 4);
+        let got: Vec<(&str, String, bool)> = ops
+            .iter()
+            .flat_map(|op| op.accesses.iter().map(move |a| (a, op.denied)))
+            .map(|(a, denied)| (a.kind.name(), a.path.display().to_string(), denied))
+            .collect();
+        let want = [
+            ("execute", "/usr/bin/make", false),
+            ("read", "/home/u/proj/Makefile", false),
+            ("write", "/tmp/a bé", true),
+            ("create", "/tmp/a bé", true),
+            ("truncate", "/tmp/a bé", true),
+            ("delete_dir", "/home/u/proj/build/x.o", false),
+        ];
+        let want: Vec<(&str, String, bool)> = want
+            .iter()
+            .map(|(k, p, d)| (*k, p.to_string(), *d))
+            .collect();
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn test_parse_access_list() {
+        let set = parse_access_list(&["write_file".into(), " remove_file".into()]).unwrap();
+        assert_eq!(set, AccessFs::WriteFile | AccessFs::RemoveFile);
+        assert_eq!(
+            parse_access_list(&[]).unwrap().len(),
+            PERMISSION_NAMES.len()
+        );
+        let err = parse_access_list(&["write".into()])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown access 'write'"), "{}", err);
+    }
+
+    #[test]
+    fn test_nested_mounts_from_mountinfo() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+40 22 8:17 /models /aisb-none/proj/data rw shared:9 master:3 - ext4 /dev/sdb1 rw
+41 22 0:33 / /aisb-none/proj/my\\040dir rw - tmpfs tmpfs rw
+42 22 0:34 / /aisb-none/other rw - tmpfs tmpfs rw
+43 22 0:35 / /aisb-none/proj/.cache rw - tmpfs tmpfs rw
+";
+        let plan = PlannedRuleset {
+            handled: AccessFs::ReadFile | AccessFs::WriteFile,
+            rules: vec![
+                (
+                    PathBuf::from("/aisb-none/proj"),
+                    BitFlags::from(AccessFs::ReadFile),
+                ),
+                (
+                    PathBuf::from("/aisb-none/proj/data"),
+                    BitFlags::from(AccessFs::WriteFile),
+                ),
+            ],
+        };
+        let expected = [PathBuf::from("/aisb-none/proj/.cache")];
+        let nested = nested_mounts(&plan, mountinfo, &expected);
+        assert_eq!(nested.len(), 2);
+        assert_eq!(nested[0].mount.describe_source(), "/dev/sdb1[/models]");
+        assert_eq!(nested[0].under, PathBuf::from("/aisb-none/proj"));
+        assert_eq!(nested[0].access, AccessFs::ReadFile | AccessFs::WriteFile);
+        assert_eq!(
+            nested[1].mount.mount_point,
+            PathBuf::from("/aisb-none/proj/my dir")
+        );
+    }
+
+    #[test]
+    fn test_seccomp_denied_list() {
+        let spec: SeccompSpec =
+            serde_yaml::from_str("allow: [ptrace, mount]\ndeny: [chroot, bpf]\n").unwrap();
+        assert_eq!(spec.preset, SeccompPreset::Default);
+        let names: Vec<String> = spec.denied().unwrap().into_iter().map(|(n, _)| n).collect();
+        assert!(!names.iter().any(|n| n == "ptrace" || n == "mount"));
+        assert_eq!(names.iter().filter(|n| *n == "bpf").count(), 1);
+        assert_eq!(names.last().map(String::as_str), Some("chroot"));
+
+        let none: SeccompSpec = serde_yaml::from_str("preset: none\n").unwrap();
+        assert!(SeccompFilter::compile(&none).unwrap().is_none());
+
+        let bad: SeccompSpec = serde_yaml::from_str("deny: [no_such_call]\n").unwrap();
+        assert!(bad.denied().is_err());
+    }
+
+    #[test]
+    fn test_stdio_spec_parsing() {
+        let yaml =
+            "stdin: null\nstdout: {append: /tmp/out.log}\nstderr: inherit\ntee: /tmp/all.log\n";
+        let spec: StdioSpec = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(spec.stdin, Some(StdioTarget::Mode(StdioMode::Null)));
+        assert_eq!(
+            spec.stdout,
+            Some(StdioTarget::Append {
+                append: "/tmp/out.log".into()
+            })
+        );
+        assert_eq!(spec.stderr, Some(StdioTarget::Mode(StdioMode::Inherit)));
+        assert_eq!(spec.tee.as_deref(), Some("/tmp/all.log"));
+
+        let spec: StdioSpec = serde_yaml::from_str("stdout: {file: out.txt}\n").unwrap();
+        assert!(spec.stdin.is_none());
+        assert!(open_stdio(&StdioSpec {
+            stdin: Some(StdioTarget::Append {
+                append: "/tmp/x".into()
+            }),
+            ..StdioSpec::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_read_elf_of_current_exe() {
+        let exe = std::env::current_exe().unwrap();
+        let info = read_elf(&exe).expect("test binary is an ELF file");
+        assert_eq!(info.class64, cfg!(target_pointer_width = "64"));
+        if let Some(interp) = info.interp.as_ref() {
+            assert!(interp.starts_with('/'));
+            assert!(!info.needed.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_read_elf_rejects_truncated_and_lying_headers() {
+        let dir = std::env::temp_dir().join(format!("ai-sandbox-badelf-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let elf64 = |patch: &dyn Fn(&mut Vec<u8>)| {
+            let mut b = vec![0u8; 64];
+            b[..4].copy_from_slice(b"\x7fELF");
+            b[4] = 2;
+            b[5] = 1;
+            patch(&mut b);
+            b
+        };
+        let cases: Vec<(&str, Vec<u8>)> = vec![
+            ("short", b"\x7fELF\x02\x01".to_vec()),
+            // e_phentsize = 1, e_phnum = 1: smaller than an Elf64_Phdr.
+            (
+                "tiny_phentsize",
+                elf64(&|b| {
+                    b[32] = 64;
+                    b[54] = 1;
+                    b[56] = 1;
+                }),
+            ),
+            // Program headers claimed past the end of the file.
+            (
+                "phoff_past_end",
+                elf64(&|b| {
+                    b[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+                    b[54] = 56;
+                    b[56] = 1;
+                }),
+            ),
+            // PT_INTERP and PT_DYNAMIC with huge sizes and offsets.
+            ("huge_segments", {
+                let mut b = elf64(&|b| {
+                    b[32] = 64;
+                    b[54] = 56;
+                    b[56] = 2;
+                });
+                for p_type in [3u32, 2] {
+                    let mut ph = vec![0u8; 56];
+                    ph[..4].copy_from_slice(&p_type.to_le_bytes());
+                    ph[8..16].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+                    ph[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+                    b.extend(ph);
+                }
+                b
+            }),
+        ];
+        for (name, bytes) in cases {
+            let path = dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            let info = read_elf(&path);
+            assert!(info.as_ref().is_none_or(|i| i.interp.is_none()), "{}", name);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_preflight_follows_shebang_and_reports_denials() {
+        let dir = std::env::temp_dir().join(format!("ai-sandbox-preflight-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("tool.sh");
+        fs::write(&script, "#!/bin/sh\necho hi\n").unwrap();
+
+        let cmd = vec![script.to_string_lossy().into_owned()];
+        let report = preflight_command(&cmd, None).unwrap();
+        let real_sh = fs::canonicalize("/bin/sh").unwrap();
+        assert!(report.requirements.iter().any(|r| r.path == real_sh));
+
+        let plan = plan_ruleset_root(&dir.to_string_lossy(), true).unwrap();
+        let script_req = report
+            .requirements
+            .iter()
+            .find(|r| r.path == fs::canonicalize(&script).unwrap())
+            .unwrap();
+        assert!(plan
+            .denied_for(&script_req.path, script_req.need)
+            .is_empty());
+        assert!(plan
+            .denied_for(&real_sh, AccessFs::Execute.into())
+            .contains(AccessFs::Execute));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_protect_splits_writable_group() {
+        let root = std::env::temp_dir().join(format!("ai-sandbox-protect-{}", std::process::id()));
+        fs::create_dir_all(root.join(".git/hooks")).unwrap();
+        fs::create_dir_all(root.join(".github/workflows")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join(".env.local"), "TOKEN=x").unwrap();
+
+        let group = AccessRootGroup {
+            paths: vec![root.to_string_lossy().into_owned()],
+            permissions: Permissions {
+                read_file: Some(true),
+                read_dir: Some(true),
+                write_file: Some(true),
+                remove_file: Some(true),
+                ..Permissions::default()
+            },
+        };
+        let allowed = access_from_permissions(&group.permissions);
+        let mut access_roots = HashMap::new();
+        access_roots.insert("projects".to_string(), group.clone());
+        let profile = Profile {
+            access_roots,
+            protect: vec![Protect::Vcs, Protect::Dotenv, Protect::CiConfig],
+            ..Profile::default()
+        };
+
+        let protected = plan_protections(&profile).unwrap();
+        let rules = group_rules(&group.paths, allowed, &protected);
+        let access_of = |p: PathBuf| rules.iter().find(|(rp, _)| *rp == p).map(|(_, a)| *a);
+
+        assert_eq!(access_of(root.join("src")), Some(allowed));
+        assert_eq!(
+            access_of(root.join(".git")),
+            Some(AccessFs::ReadFile | AccessFs::ReadDir)
+        );
+        assert_eq!(
+            access_of(root.join(".github/workflows")),
+            Some(AccessFs::ReadFile | AccessFs::ReadDir)
+        );
+        assert_eq!(access_of(root.join(".env.local")), None);
+        assert_eq!(
+            access_of(root.clone()),
+            Some(BitFlags::from(AccessFs::ReadDir))
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_protect_handles_creation_rights() {
+        let root =
+            std::env::temp_dir().join(format!("ai-sandbox-protect-mk-{}", std::process::id()));
+        fs::create_dir_all(root.join(".git/hooks")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        let group = AccessRootGroup {
+            paths: vec![root.to_string_lossy().into_owned()],
+            permissions: Permissions {
+                read_file: Some(true),
+                read_dir: Some(true),
+                write_file: Some(true),
+                ..Permissions::default()
+            },
+        };
+        let mut profile = Profile {
+            access_roots: HashMap::from([("projects".to_string(), group)]),
+            ..Profile::default()
+        };
+        let make = AccessFs::MakeReg | AccessFs::MakeDir | AccessFs::MakeSym;
+        assert!((plan_ruleset_profile(&profile).unwrap().handled & make).is_empty());
+
+        profile.protect = vec![Protect::Vcs];
+        let plan = plan_ruleset_profile(&profile).unwrap();
+        assert!(plan.handled.contains(make));
+        assert_eq!(plan.denied_for(&root.join(".git/hooks"), make), make);
+        assert!(plan.denied_for(&root.join("src"), make).is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_protect_split_skips_symlinks() {
+        let base =
+            std::env::temp_dir().join(format!("ai-sandbox-protect-ln-{}", std::process::id()));
+        let root = base.join("proj");
+        let outside = base.join("outside");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(outside.join("workflows")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join(".github")).unwrap();
+        std::os::unix::fs::symlink(outside.join("env"), root.join(".env")).unwrap();
+        let group = AccessRootGroup {
+            paths: vec![root.to_string_lossy().into_owned()],
+            permissions: Permissions {
+                read_file: Some(true),
+                read_dir: Some(true),
+                write_file: Some(true),
+                remove_file: Some(true),
+                ..Permissions::default()
+            },
+        };
+        let profile = Profile {
+            access_roots: HashMap::from([("projects".to_string(), group.clone())]),
+            protect: vec![Protect::Vcs, Protect::Dotenv, Protect::CiConfig],
+            ..Profile::default()
+        };
+
+        let protected = plan_protections(&profile).unwrap();
+        let paths: Vec<&PathBuf> = protected.iter().map(|t| &t.path).collect();
+        assert_eq!(paths, vec![&root.join(".git")]);
+        let rules = group_rules(&group.paths, group_access(&profile, &group), &protected);
+        assert!(rules.iter().any(|(p, _)| *p == root.join("src")));
+        for (p, _) in &rules {
+            assert!(
+                fs::symlink_metadata(p).is_ok_and(|m| !m.file_type().is_symlink()),
+                "rule on symlink {}",
+                p.display()
+            );
+        }
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
+
+/// Effective launch settings after merging CLI flags over the profile.
+struct LaunchOptions {
+    exec_mode: ExecMode,
+    limits: Limits,
+    hardening: Hardening,
+    /// Opened before restrict_self, see `open_stdio`.
+    stdio: OpenedStdio,
+    /// Opened before restrict_self as well; taken by `run_command`.
+    pty: Option<Pty>,
+    /// Removed when the options are dropped at the end of `run_command`.
+    tmpdir: Option<PrivateTmp>,
+    /// Set with `namespaces.pid`; the spawned process becomes its init.
+    pid_init: Option<PidInit>,
+    capabilities: Option<CapabilityDrop>,
+    seccomp: Option<SeccompFilter>,
+}
+
+fn build_command(
+    cmd: &[String],
+    spec: Option<&CommandSpec>,
+    opts: &LaunchOptions,
+) -> Result<Command> {
+    let (bin, args) = cmd
+        .split_first()
+        .ok_or_else(|| anyhow!("command vector is empty"))?;
+
+    let mut cmdp = Command::new(bin);
+    cmdp.args(args);
+    if let Some(spec) = spec {
+        if let Some(wd) = spec.working_dir.as_ref() {
+            let cwd = normalize_path(wd)?;
+            if !std::path::Path::new(&cwd).is_dir() {
+                return Err(anyhow!(
+                    "working_dir does not exist or is not a directory: {}",
+                    cwd
+                ));
+            }
+            cmdp.current_dir(cwd);
+        }
+    }
+    let envs = resolve_env(spec, env::vars())?;
+    cmdp.env_clear();
+    cmdp.envs(envs.vars);
+    if let Some(tmp) = opts.tmpdir.as_ref() {
+        for name in ["TMPDIR", "TMP", "TEMP"] {
+            cmdp.env(name, &tmp.path);
+        }
+    }
+
+    let rlimits = opts.limits.rlimits()?;
+    if !rlimits.is_empty() {
+        // SAFETY: the closure only calls setrlimit(2), which is async-signal-safe.
+        unsafe {
+            cmdp.pre_exec(move || apply_rlimits(&rlimits));
+        }
+    }
+    apply_hardening(&mut cmdp, &opts.hardening, opts.exec_mode)?;
+    if let Some(stdin) = opts.stdio.stdin.to_stdio()? {
+        cmdp.stdin(stdin);
+    }
+    if let Some(stdout) = opts.stdio.stdout.to_stdio()? {
+        cmdp.stdout(stdout);
+    }
+    if let Some(stderr) = opts.stdio.stderr.to_stdio()? {
+        cmdp.stderr(stderr);
+    }
+    Ok(cmdp)
+}
+
+fn run_command(
+    cmd: &[String],
+    spec: Option<&CommandSpec>,
+    mut opts: LaunchOptions,
+) -> Result<RunOutcome> {
+    let pty = opts.pty.take();
+    let limits = &opts.limits;
+    let mut cmdp = build_command(cmd, spec, &opts)?;
+    // A pseudo-terminal needs its own session (setsid) as well.
+    let new_session = opts.hardening.new_session.unwrap_or(false) || pty.is_some();
+    if !new_session {
+        // Own process group, so forwarded signals reach the whole job.
+        // (setsid() in new_session mode creates one as well.)
+        cmdp.process_group(0);
+    }
+    let kill_sequence = limits.kill_signals()?;
+    let relay = limits.idle_timeout.is_some() || opts.stdio.tee.is_some() || pty.is_some();
+    let mut sinks = (Vec::new(), Vec::new());
+    if relay {
+        // Output is relayed through the launcher to notice idleness and
+        // to copy it into the tee file.
+        match pty.as_ref() {
+            Some(pty) => pty.attach(&mut cmdp)?,
+            None => {
+                cmdp.stdout(Stdio::piped()).stderr(Stdio::piped());
+            }
+        }
+        sinks
+            .0
+            .push(opts.stdio.stdout.writer(OutputStream::Stdout)?);
+        sinks
+            .1
+            .push(opts.stdio.stderr.writer(OutputStream::Stderr)?);
+        if let Some(tee) = opts.stdio.tee.as_ref() {
+            sinks.0.push(Box::new(tee.try_clone()?));
+            sinks.1.push(Box::new(tee.try_clone()?));
+        }
+    }
+    if let Some(init) = opts.pid_init.take() {
+        // Registered last: everything before it also applies to the init.
+        init.attach(&mut cmdp);
+    }
+    if let Some(caps) = opts.capabilities.as_ref() {
+        caps.attach(&mut cmdp);
+    }
+    if let Some(filter) = opts.seccomp.as_ref() {
+        filter.attach(&mut cmdp);
+    }
+    let raw_mode = pty.as_ref().and_then(|p| p.prepare_terminal());
+
+    install_forwarding_handlers();
+    let usage_before = ChildUsage::collect();
+    let started = SystemTime::now();
+    let spawned = cmdp.spawn();
+    // Close the launcher's copies of the pty slave, so reading the master
+    // reports EOF once the command and its descendants are gone.
+    drop(cmdp);
+    let mut child = spawned?;
+    let pid = child.id() as libc::pid_t;
+    set_child_pid(pid);
+    let foreground = if new_session {
+        None
+    } else {
+        TerminalForeground::acquire(pid)
+    };
+    let mut streams: Vec<(Source, Vec<Sink>)> = Vec::new();
+    if let Some(pty) = pty {
+        streams.push((Box::new(pty.master.try_clone()?), sinks.0));
+        spawn_stdin_relay(pty.master);
+    } else {
+        if let Some(out) = child.stdout.take() {
+            streams.push((Box::new(out), sinks.0));
+        }
+        if let Some(err) = child.stderr.take() {
+            streams.push((Box::new(err), sinks.1));
+        }
+    }
+    let activity = OutputActivity::relay(streams);
+
+    let status = wait_with_limits(
+        &mut child,
+        pid,
+        limits,
+        &kill_sequence,
+        &activity,
+        foreground.as_ref(),
+    );
+    drop(foreground);
+    set_child_pid(0);
+    activity.finish();
+    drop(raw_mode);
+    let (status, expired) = status?;
+
+    let mut outcome = RunOutcome {
+        code: 1,
+        signal: status.signal(),
+        stopped: None,
+        started,
+        ended: SystemTime::now(),
+        usage: ChildUsage::collect().since(usage_before),
+    };
+    if let Some(reason) = expired {
+        warn!("command stopped: {}", reason);
+        outcome.code = EXIT_TIMEOUT;
+        outcome.stopped = Some(reason);
+    } else if let Some(code) = status.code() {
+        outcome.code = code;
+    } else if let Some(sig) = status.signal() {
+        // Shell convention: 128 + signal number.
+        warn!("process terminated by signal {}", sig);
+        outcome.code = 128 + sig;
+    } else {
+        error!("process terminated abnormally");
+    }
+    Ok(outcome)
+}
+
+/// How the supervised command ended: the launcher's exit code plus the
+/// details recorded by `--report`.
+struct RunOutcome {
+    code: i32,
+    signal: Option<i32>,
+    /// Set when a timeout or idle timeout stopped the command.
+    stopped: Option<String>,
+    started: SystemTime,
+    ended: SystemTime,
+    usage: ChildUsage,
+}
+
+/// Exit code used when the command was stopped by `timeout` or
+/// `idle_timeout` (same as coreutils `timeout`).
+const EXIT_TIMEOUT: i32 = 124;
+const WAIT_POLL: Duration = Duration::from_millis(50);
+
+/// Wait for the child, enforcing wall-clock and idle timeouts. Returns the
+/// exit status and, when a limit expired, a description of it.
+fn wait_with_limits(
+    child: &mut Child,
+    pgid: libc::pid_t,
+    limits: &Limits,
+    kill_sequence: &[libc::c_int],
+    activity: &OutputActivity,
+    foreground: Option<&TerminalForeground>,
+) -> Result<(ExitStatus, Option<String>)> {
+    let started = Instant::now();
+    let timeout = limits.timeout.map(Duration::from_secs);
+    let idle_timeout = limits.idle_timeout.map(Duration::from_secs);
+    if timeout.is_none() && idle_timeout.is_none() {
+        let status = wait_child(pgid, false, foreground)?;
+        return Ok((
+            status.ok_or_else(|| anyhow!("waitpid returned no status"))?,
+            None,
+        ));
+    }
+    loop {
+        if let Some(status) = wait_child(pgid, true, foreground)? {
+            return Ok((status, None));
+        }
+        let expired = if timeout.is_some_and(|t| started.elapsed() >= t) {
+            Some(format!(
+                "timeout of {}s expired",
+                limits.timeout.unwrap_or(0)
+            ))
+        } else if idle_timeout.is_some_and(|t| activity.idle_for() >= t) {
+            Some(format!(
+                "no output for {}s (idle_timeout)",
+                limits.idle_timeout.unwrap_or(0)
+            ))
+        } else {
+            None
+        };
+        if let Some(reason) = expired {
+            let grace = Duration::from_secs(limits.kill_grace.unwrap_or(DEFAULT_KILL_GRACE));
+            let status = terminate_group(child, pgid, kill_sequence, grace)?;
+            return Ok((status, Some(reason)));
+        }
+        std::thread::sleep(WAIT_POLL);
+    }
+}
+
+/// waitpid(2) on the child, also reporting stops: when the child's group
+/// is stopped from the terminal (Ctrl-Z, or reading it from the
+/// background), the launcher stops along with it (see
+/// `TerminalForeground::suspend`) and waits again once continued.
+/// Returns None while the child runs, which only happens with `nohang`.
+fn wait_child(
+    pid: libc::pid_t,
+    nohang: bool,
+    foreground: Option<&TerminalForeground>,
+) -> Result<Option<ExitStatus>> {
+    let flags = libc::WUNTRACED | if nohang { libc::WNOHANG } else { 0 };
+    loop {
+        let mut status = 0;
+        // SAFETY: waitpid on our own child with a valid status pointer.
+        let ret = unsafe { libc::waitpid(pid, &mut status, flags) };
+        if ret < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
+        }
+        if ret == 0 {
+            return Ok(None);
+        }
+        if libc::WIFSTOPPED(status) {
+            if let Some(fg) = foreground {
+                fg.suspend(pid);
+            }
+            continue;
+        }
+        return Ok(Some(ExitStatus::from_raw(status)));
+    }
+}
+
+/// Send each signal to the process group in turn, waiting `grace` after
+/// each one; the last signal is KILL (see `Limits::kill_signals`), so the
+/// final wait does not depend on the command's cooperation.
+fn terminate_group(
+    child: &mut Child,
+    pgid: libc::pid_t,
+    signals: &[libc::c_int],
+    grace: Duration,
+) -> Result<ExitStatus> {
+    for (i, sig) in signals.iter().enumerate() {
+        info!("sending signal {} to process group {}", sig, pgid);
+        // SAFETY: plain kill(2) on the child's process group.
+        unsafe {
+            libc::kill(-pgid, *sig);
+        }
+        if i + 1 == signals.len() {
+            break;
+        }
+        let sent = Instant::now();
+        while sent.elapsed() < grace {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+            std::thread::sleep(WAIT_POLL);
+        }
+    }
+    Ok(child.wait()?)
+}
+
+/// Tracks when the child last wrote to stdout/stderr. Only active when the
+/// child's output is relayed (idle_timeout, tee, tty); otherwise it never
+/// reports idle.
+struct OutputActivity {
+    last: Option<Arc<Mutex<Instant>>>,
+    done: Option<mpsc::Receiver<()>>,
+    relays: usize,
+}
+
+impl OutputActivity {
+    fn relay(streams: Vec<(Source, Vec<Sink>)>) -> Self {
+        let last = Arc::new(Mutex::new(Instant::now()));
+        let (tx, rx) = mpsc::channel();
+        let relays = streams.len();
+        for (from, to) in streams {
+            spawn_relay(from, to, last.clone(), tx.clone());
+        }
+        if relays == 0 {
+            return OutputActivity {
+                last: None,
+                done: None,
+                relays,
+            };
+        }
+        OutputActivity {
+            last: Some(last),
+            done: Some(rx),
+            relays,
+        }
+    }
+
+    fn idle_for(&self) -> Duration {
+        match &self.last {
+            Some(last) => last.lock().map(|t| t.elapsed()).unwrap_or_default(),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Give relays a moment to drain remaining output. Descendants that keep
+    /// the pipes open must not block the launcher forever.
+    fn finish(self) {
+        if let Some(done) = self.done {
+            for _ in 0..self.relays {
+                if done.recv_timeout(Duration::from_millis(500)).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+type Source = Box<dyn Read + Send>;
+type Sink = Box<dyn Write + Send>;
+
+/// Copy everything read from the child to each sink; a sink that fails is
+/// dropped, and relaying stops once none are left.
+fn spawn_relay<R>(mut from: R, mut to: Vec<Sink>, last: Arc<Mutex<Instant>>, done: mpsc::Sender<()>)
+where
+    R: Read + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        loop {
+            match from.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if let Ok(mut t) = last.lock() {
+                        *t = Instant::now();
+                    }
+                    to.retain_mut(|w| w.write_all(&buf[..n]).and_then(|_| w.flush()).is_ok());
+                    if to.is_empty() {
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = done.send(());
+    });
+}
+
+/// Replace the launcher with the command (execve). Landlock restrictions
+/// applied earlier via `restrict_self` carry over to the new program image.
+fn exec_command(cmd: &[String], spec: Option<&CommandSpec>, opts: &LaunchOptions) -> anyhow::Error {
+    let mut cmdp = match build_command(cmd, spec, opts) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+    if let Some(caps) = opts.capabilities.as_ref() {
+        caps.attach(&mut cmdp);
+    }
+    if let Some(filter) = opts.seccomp.as_ref() {
+        filter.attach(&mut cmdp);
+    }
+    let err = cmdp.exec();
+    anyhow!("failed to exec {}: {}", cmd[0], err)
+}
+
+// ---------------- Preflight ----------------
+
+const LD_SO_CACHE: &str = "/etc/ld.so.cache";
+const DEFAULT_LIB_DIRS: &[&str] = &[
+    "/lib64",
+    "/usr/lib64",
+    "/lib/x86_64-linux-gnu",
+    "/usr/lib/x86_64-linux-gnu",
+    "/lib/aarch64-linux-gnu",
+    "/usr/lib/aarch64-linux-gnu",
+    "/lib",
+    "/usr/lib",
+];
+const MAX_SHEBANG_DEPTH: usize = 4;
+
+/// A file the command needs at exec time and the rights it needs on it.
+#[derive(Debug)]
+struct Requirement {
+    path: PathBuf,
+    need: BitFlags<AccessFs>,
+    reason: String,
+    /// Denial is survivable (e.g. the loader falls back without ld.so.cache).
+    optional: bool,
+}
+
+#[derive(Debug, Default)]
+struct PreflightReport {
+    requirements: Vec<Requirement>,
+    /// Problems found while resolving (missing libraries, unreadable files).
+    notes: Vec<String>,
+}
+
+impl PreflightReport {
+    fn require(&mut self, path: &std::path::Path, need: BitFlags<AccessFs>, reason: String) {
+        self.push(path, need, reason, false);
+    }
+
+    fn push(
+        &mut self,
+        path: &std::path::Path,
+        need: BitFlags<AccessFs>,
+        reason: String,
+        optional: bool,
+    ) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        match self.requirements.iter_mut().find(|r| r.path == path) {
+            Some(r) => {
+                r.need.insert(need);
+                r.optional &= optional;
+            }
+            None => self.requirements.push(Requirement {
+                path,
+                need,
+                reason,
+                optional,
+            }),
+        }
+    }
+
+    fn seen(&self, path: &std::path::Path) -> bool {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.requirements.iter().any(|r| r.path == path)
+    }
+}
+
+/// Resolve what exec of `cmd` will touch: the binary (via PATH), script
+/// interpreters (shebangs), the ELF interpreter and shared libraries.
+fn preflight_command(cmd: &[String], spec: Option<&CommandSpec>) -> Result<PreflightReport> {
+    let mut report = PreflightReport::default();
+    let bin = match cmd.first() {
+        Some(b) => b,
+        None => return Ok(report),
+    };
+    let envs = resolve_env(spec, env::vars())?;
+    let cwd = match spec.and_then(|s| s.working_dir.as_ref()) {
+        Some(wd) => PathBuf::from(normalize_path(wd)?),
+        None => env::current_dir()?,
+    };
+    let path_var = envs.vars.get("PATH").map(String::as_str);
+    let ld_library_path = envs.vars.get("LD_LIBRARY_PATH").map(String::as_str);
+
+    let resolved = if bin.contains('/') {
+        Some(cwd.join(bin))
+    } else {
+        search_path(bin, path_var)
+    };
+    match resolved {
+        Some(path) => preflight_file(
+            &path,
+            "command".to_string(),
+            (path_var, ld_library_path),
+            0,
+            &mut report,
+        ),
+        None => report
+            .notes
+            .push(format!("command '{}' not found in PATH", bin)),
+    }
+    Ok(report)
+}
+
+fn search_path(name: &str, path_var: Option<&str>) -> Option<PathBuf> {
+    path_var?
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(|d| PathBuf::from(d).join(name))
+        .find(|p| p.is_file())
+}
+
+/// `search` is the command's (PATH, LD_LIBRARY_PATH).
+fn preflight_file(
+    path: &std::path::Path,
+    reason: String,
+    search: (Option<&str>, Option<&str>),
+    depth: usize,
+    report: &mut PreflightReport,
+) {
+    let (path_var, ld_library_path) = search;
+    report.require(path, AccessFs::Execute.into(), reason);
+    let mut head = [0u8; 256];
+    let n = match fs::File::open(path).and_then(|mut f| f.read(&mut head)) {
+        Ok(n) => n,
+        Err(e) => {
+            report
+                .notes
+                .push(format!("cannot read {}: {}", path.display(), e));
+            return;
+        }
+    };
+    let head = &head[..n];
+    if let Some(line) = head.strip_prefix(b"#!") {
+        // The interpreter opens the script for reading.
+        report.require(path, AccessFs::ReadFile.into(), String::new());
+        if depth >= MAX_SHEBANG_DEPTH {
+            report
+                .notes
+                .push(format!("shebang chain too deep at {}", path.display()));
+            return;
+        }
+        let line = line.split(|b| *b == b'\n').next().unwrap_or_default();
+        let line = String::from_utf8_lossy(line);
+        let mut words = line.split_whitespace();
+        let interp = match words.next() {
+            Some(i) => PathBuf::from(i),
+            None => return,
+        };
+        let reason = format!("interpreter of {}", path.display());
+        preflight_file(&interp, reason, search, depth + 1, report);
+        // `#!/usr/bin/env prog`: env runs prog from PATH.
+        if interp.file_name().is_some_and(|n| n == "env") {
+            if let Some(prog) = words.find(|w| !w.starts_with('-')) {
+                match search_path(prog, path_var) {
+                    Some(p) => preflight_file(
+                        &p,
+                        format!("program run by env for {}", path.display()),
+                        search,
+                        depth + 1,
+                        report,
+                    ),
+                    None => report.notes.push(format!(
+                        "'{}' (shebang of {}) not found in PATH",
+                        prog,
+                        path.display()
+                    )),
+                }
+            }
+        }
+    } else if head.starts_with(b"\x7fELF") {
+        preflight_elf(path, ld_library_path, report);
+    }
+}
+
+fn preflight_elf(
+    exe: &std::path::Path,
+    ld_library_path: Option<&str>,
+    report: &mut PreflightReport,
+) {
+    let info = match read_elf(exe) {
+        Some(i) => i,
+        None => {
+            report
+                .notes
+                .push(format!("cannot parse ELF headers of {}", exe.display()));
+            return;
+        }
+    };
+    if let Some(interp) = info.interp.as_ref() {
+        report.require(
+            std::path::Path::new(interp),
+            AccessFs::Execute.into(),
+            format!("ELF interpreter of {}", exe.display()),
+        );
+    }
+    if info.needed.is_empty() {
+        return;
+    }
+    let cache = read_ld_so_cache();
+    if !cache.is_empty() {
+        report.push(
+            std::path::Path::new(LD_SO_CACHE),
+            AccessFs::ReadFile.into(),
+            "read by the dynamic loader; it falls back to default paths".to_string(),
+            true,
+        );
+    }
+
+    // Breadth-first over DT_NEEDED; each object's own RPATH/RUNPATH applies.
+    let mut queue: Vec<(PathBuf, ElfInfo)> = vec![(exe.to_path_buf(), info)];
+    while let Some((object, info)) = queue.pop() {
+        let origin = object.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        for lib in &info.needed {
+            match find_library(lib, &info, &origin, ld_library_path, &cache) {
+                Some(found) => {
+                    if report.seen(&found) {
+                        continue;
+                    }
+                    report.require(
+                        &found,
+                        AccessFs::ReadFile.into(),
+                        format!("library {} needed by {}", lib, object.display()),
+                    );
+                    if let Some(dep) = read_elf(&found) {
+                        queue.push((found, dep));
+                    }
+                }
+                None => report.notes.push(format!(
+                    "library {} needed by {} not found",
+                    lib,
+                    object.display()
+                )),
+            }
+        }
+    }
+}
+
+/// ld.so search order: DT_RPATH (without DT_RUNPATH), LD_LIBRARY_PATH,
+/// DT_RUNPATH, ld.so.cache, default directories. Candidates of a different
+/// ELF class or machine are skipped, as the loader does.
+fn find_library(
+    name: &str,
+    requester: &ElfInfo,
+    origin: &std::path::Path,
+    ld_library_path: Option<&str>,
+    cache: &[(String, String)],
+) -> Option<PathBuf> {
+    let matches = |p: &std::path::Path| {
+        read_elf(p)
+            .is_some_and(|i| i.class64 == requester.class64 && i.machine == requester.machine)
+    };
+    if name.contains('/') {
+        return Some(PathBuf::from(name)).filter(|p| matches(p));
+    }
+    let expand = |dirs: &[String]| -> Vec<PathBuf> {
+        dirs.iter()
+            .flat_map(|d| d.split(':'))
+            .filter(|d| !d.is_empty())
+            .map(|d| {
+                PathBuf::from(
+                    d.replace("$ORIGIN", &origin.to_string_lossy())
+                        .replace("${ORIGIN}", &origin.to_string_lossy()),
+                )
+            })
+            .collect()
+    };
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if requester.runpath.is_empty() {
+        dirs.extend(expand(&requester.rpath));
+    }
+    if let Some(llp) = ld_library_path {
+        dirs.extend(expand(&[llp.to_string()]));
+    }
+    dirs.extend(expand(&requester.runpath));
+    for dir in &dirs {
+        let candidate = dir.join(name);
+        if matches(&candidate) {
+            return Some(candidate);
+        }
+    }
+    for (soname, path) in cache {
+        if soname == name && matches(std::path::Path::new(path)) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    DEFAULT_LIB_DIRS
+        .iter()
+        .map(|d| PathBuf::from(d).join(name))
+        .find(|p| matches(p))
+}
+
+#[derive(Debug, Default)]
+struct ElfInfo {
+    class64: bool,
+    machine: u16,
+    interp: Option<String>,
+    needed: Vec<String>,
+    rpath: Vec<String>,
+    runpath: Vec<String>,
+}
+
+/// Read PT_INTERP and the DT_NEEDED/DT_RPATH/DT_RUNPATH entries of an ELF
+/// file without loading it whole.
+fn read_elf(path: &std::path::Path) -> Option<ElfInfo> {
+    use std::os::unix::fs::FileExt;
+
+    const PT_LOAD: u32 = 1;
+    const PT_DYNAMIC: u32 = 2;
+    const PT_INTERP: u32 = 3;
+    const DT_NULL: u64 = 0;
+    const DT_NEEDED: u64 = 1;
+    const DT_STRTAB: u64 = 5;
+    const DT_RPATH: u64 = 15;
+    const DT_RUNPATH: u64 = 29;
+
+    let file = fs::File::open(path).ok()?;
+    let file_len = file.metadata().ok()?.len();
+    // Sizes come from the file itself: never read (or allocate) past its end.
+    let read_at = |off: u64, len: usize| -> Option<Vec<u8>> {
+        if off.checked_add(len as u64)? > file_len {
+            return None;
+        }
+        let mut buf = vec![0u8; len];
+        file.read_exact_at(&mut buf, off).ok()?;
+        Some(buf)
+    };
+    let ident = read_at(0, 64)?;
+    if &ident[..4] != b"\x7fELF" {
+        return None;
+    }
+    let class64 = ident[4] == 2;
+    let le = ident[5] == 1;
+    let bytes_at = |b: &[u8], o: usize, n: usize| b.get(o..o.checked_add(n)?).map(<[u8]>::to_vec);
+    let u16_at = |b: &[u8], o: usize| -> Option<u16> {
+        let v = bytes_at(b, o, 2)?.try_into().ok()?;
+        Some(if le {
+            u16::from_le_bytes(v)
+        } else {
+            u16::from_be_bytes(v)
+        })
+    };
+    let u32_at = |b: &[u8], o: usize| -> Option<u32> {
+        let v = bytes_at(b, o, 4)?.try_into().ok()?;
+        Some(if le {
+            u32::from_le_bytes(v)
+        } else {
+            u32::from_be_bytes(v)
+        })
+    };
+    let u64_at = |b: &[u8], o: usize| -> Option<u64> {
+        let v = bytes_at(b, o, 8)?.try_into().ok()?;
+        Some(if le {
+            u64::from_le_bytes(v)
+        } else {
+            u64::from_be_bytes(v)
+        })
+    };
+    // Address-sized field: 8 bytes in ELF64, 4 in ELF32.
+    let word_at = |b: &[u8], o: usize| {
+        if class64 {
+            u64_at(b, o)
+        } else {
+            u32_at(b, o).map(u64::from)
+        }
+    };
+
+    let machine = u16_at(&ident, 18)?;
+    let (phoff, phentsize, phnum) = if class64 {
+        (
+            u64_at(&ident, 32)?,
+            u16_at(&ident, 54)?,
+            u16_at(&ident, 56)?,
+        )
+    } else {
+        (
+            u64::from(u32_at(&ident, 28)?),
+            u16_at(&ident, 42)?,
+            u16_at(&ident, 44)?,
+        )
+    };
+    // sizeof(Elf64_Phdr) / sizeof(Elf32_Phdr): the fields read below.
+    let min_phentsize = if class64 { 56 } else { 32 };
+    if (phentsize as usize) < min_phentsize {
+        return None;
+    }
+    let phdrs = read_at(phoff, phentsize as usize * phnum as usize)?;
+
+    let mut info = ElfInfo {
+        class64,
+        machine,
+        ..ElfInfo::default()
+    };
+    // (vaddr, offset, filesz) of PT_LOAD segments, to map DT_STRTAB.
+    let mut loads: Vec<(u64, u64, u64)> = Vec::new();
+    let mut dynamic: Option<(u64, u64)> = None;
+    for i in 0..phnum as usize {
+        let ph = phdrs.get(i * phentsize as usize..)?;
+        let p_type = u32_at(ph, 0)?;
+        let (offset, vaddr, filesz) = if class64 {
+            (u64_at(ph, 8)?, u64_at(ph, 16)?, u64_at(ph, 32)?)
+        } else {
+            (
+                u64::from(u32_at(ph, 4)?),
+                u64::from(u32_at(ph, 8)?),
+                u64::from(u32_at(ph, 16)?),
+            )
+        };
+        match p_type {
+            PT_LOAD => loads.push((vaddr, offset, filesz)),
+            PT_DYNAMIC => dynamic = Some((offset, filesz)),
+            PT_INTERP => {
+                let raw = read_at(offset, filesz.min(file_len) as usize)?;
+                let end = raw.iter().position(|b| *b == 0).unwrap_or(raw.len());
+                info.interp = Some(String::from_utf8_lossy(&raw[..end]).into_owned());
+            }
+            _ => {}
+        }
+    }
+
+    let (dyn_off, dyn_size) = match dynamic {
+        Some(d) => d,
+        None => return Some(info),
+    };
+    let dyn_data = read_at(dyn_off, dyn_size.min(file_len) as usize)?;
+    let entsize = if class64 { 16 } else { 8 };
+    let mut strtab_vaddr = None;
+    let mut entries: Vec<(u64, u64)> = Vec::new();
+    for chunk in dyn_data.chunks_exact(entsize) {
+        let tag = word_at(chunk, 0)?;
+        let val = word_at(chunk, entsize / 2)?;
+        match tag {
+            DT_NULL => break,
+            DT_STRTAB => strtab_vaddr = Some(val),
+            DT_NEEDED | DT_RPATH | DT_RUNPATH => entries.push((tag, val)),
+            _ => {}
+        }
+    }
+    let strtab_vaddr = strtab_vaddr?;
+    let strtab = loads
+        .iter()
+        .find(|(v, _, sz)| {
+            strtab_vaddr >= *v && v.checked_add(*sz).is_some_and(|end| strtab_vaddr < end)
+        })
+        .and_then(|(v, off, _)| (strtab_vaddr - v).checked_add(*off))?;
+    let read_str = |off: u64| -> Option<String> {
+        let len = 4096.min(file_len.checked_sub(off)?) as usize;
+        let raw = read_at(off, len)?;
+        let end = raw.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&raw[..end]).into_owned())
+    };
+    for (tag, val) in entries {
+        let value = match strtab.checked_add(val).and_then(read_str) {
+            Some(v) => v,
+            None => continue,
+        };
+        match tag {
+            DT_NEEDED => info.needed.push(value),
+            DT_RPATH => info.rpath.push(value),
+            _ => info.runpath.push(value),
+        }
+    }
+    Some(info)
+}
+
+/// (soname, path) pairs from /etc/ld.so.cache ("glibc-ld.so.cache1.1"
+/// format, possibly embedded after the legacy "ld.so-1.7.0" table).
+fn read_ld_so_cache() -> Vec<(String, String)> {
+    const MAGIC: &[u8] = b"glibc-ld.so.cache1.1";
+    const HEADER_LEN: usize = 48;
+    const ENTRY_LEN: usize = 24;
+
+    let data = match fs::read(LD_SO_CACHE) {
+        Ok(d) => d,
+        Err(_) => return Vec::new(),
+    };
+    let base = match data.windows(MAGIC.len()).position(|w| w == MAGIC) {
+        Some(b) => b,
+        None => return Vec::new(),
+    };
+    let u32_at = |o: usize| -> Option<u32> {
+        data.get(o..o + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let cstr_at = |o: usize| -> Option<String> {
+        let rest = data.get(o..)?;
+        let end = rest.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&rest[..end]).into_owned())
+    };
+    let nlibs = u32_at(base + MAGIC.len()).unwrap_or(0) as usize;
+    let mut out = Vec::with_capacity(nlibs);
+    for i in 0..nlibs {
+        let entry = base + HEADER_LEN + i * ENTRY_LEN;
+        let (key, value) = match (u32_at(entry + 4), u32_at(entry + 8)) {
+            (Some(k), Some(v)) => (k as usize, v as usize),
+            _ => break,
+        };
+        // String offsets are relative to the start of the new-format header.
+        if let (Some(k), Some(v)) = (cstr_at(base + key), cstr_at(base + value)) {
+            out.push((k, v));
+        }
+    }
+    out
+}
+
+/// Print what exec of the command needs and what the ruleset would deny.
+fn print_preflight(report: &PreflightReport, plan: &PlannedRuleset) {
+    println!("Preflight:");
+    for r in &report.requirements {
+        let denied = plan.denied_for(&r.path, r.need);
+        let state = if denied.is_empty() {
+            "ok".to_string()
+        } else if r.optional {
+            format!("denied, optional (missing {:?})", access_names(denied))
+        } else {
+            format!("DENIED (missing {:?})", access_names(denied))
+        };
+        let reason = if r.reason.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", r.reason)
+        };
+        println!(
+            "  - {} needs {:?}: {}{}",
+            r.path.display(),
+            access_names(r.need),
+            state,
+            reason
+        );
+    }
+    for note in &report.notes {
+        println!("  note: {}", note);
+    }
+}
+
+/// Log files the ruleset would deny at exec time; returns how many.
+fn warn_preflight(report: &PreflightReport, plan: &PlannedRuleset) -> usize {
+    let mut count = 0;
+    for r in &report.requirements {
+        let denied = plan.denied_for(&r.path, r.need);
+        if !denied.is_empty() && !r.optional {
+            count += 1;
+            warn!(
+                "preflight: {} would be denied {:?} ({})",
+                r.path.display(),
+                access_names(denied),
+                if r.reason.is_empty() {
+                    "command"
+                } else {
+                    &r.reason
+                }
+            );
+        }
+    }
+    for note in &report.notes {
+        warn!("preflight: {}", note);
+    }
+    count
+}
+
+// ---------------- Learning mode ----------------
+
+/// What a traced command did to a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LearnKind {
+    Read,
+    ReadDir,
+    Execute,
+    Write,
+    Create,
+    Truncate,
+    Rename,
+    Delete,
+    DeleteDir,
+}
+
+impl LearnKind {
+    fn name(self) -> &'static str {
+        match self {
+            LearnKind::Read => "read",
+            LearnKind::ReadDir => "read_dir",
+            LearnKind::Execute => "execute",
+            LearnKind::Write => "write",
+            LearnKind::Create => "create",
+            LearnKind::Truncate => "truncate",
+            LearnKind::Rename => "rename",
+            LearnKind::Delete => "delete",
+            LearnKind::DeleteDir => "delete_dir",
+        }
+    }
+}
+
+/// A recorded access; `dir` tells whether the path was a directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LearnedAccess {
+    kind: LearnKind,
+    path: PathBuf,
+    dir: bool,
+}
+
+impl LearnedAccess {
+    /// Rights in this launcher's permission model. Creation has no right of
+    /// its own here and maps to write_file; a rename removes the source.
+    fn rights(&self) -> BitFlags<AccessFs> {
+        match self.kind {
+            LearnKind::Read => AccessFs::ReadFile.into(),
+            LearnKind::ReadDir => AccessFs::ReadDir.into(),
+            LearnKind::Execute => AccessFs::Execute | AccessFs::ReadFile,
+            LearnKind::Write | LearnKind::Create => AccessFs::WriteFile.into(),
+            LearnKind::Truncate => AccessFs::Truncate.into(),
+            LearnKind::Rename if self.dir => AccessFs::RemoveDir.into(),
+            LearnKind::Rename | LearnKind::Delete => AccessFs::RemoveFile.into(),
+            LearnKind::DeleteDir => AccessFs::RemoveDir.into(),
+        }
+    }
+}
+
+/// Prefixes that collapse into the `system` group.
+const SYSTEM_PREFIXES: &[&str] = &[
+    "/usr", "/lib", "/lib32", "/lib64", "/bin", "/sbin", "/etc", "/opt",
+];
+
+/// Kernel filesystems, each collapsed into a group of its own.
+const PSEUDO_PREFIXES: &[&str] = &["/proc", "/dev", "/sys"];
+
+/// How a traced syscall's arguments are interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CallKind {
+    /// open/openat: flags follow the path argument.
+    Open,
+    /// openat2: flags are the first field of `struct open_how`.
+    OpenHow,
+    Creat,
+    Exec,
+    Create,
+    Unlink,
+    /// unlinkat: AT_REMOVEDIR in the flags makes it an rmdir.
+    UnlinkAt,
+    Rmdir,
+    Rename,
+    Truncate,
+    /// ftruncate: the first argument is a file descriptor.
+    Ftruncate,
+}
+
+/// Argument positions of a path: (dirfd, pathname).
+type PathArg = (Option<usize>, usize);
+
+#[derive(Debug, Clone, Copy)]
+struct TracedCall {
+    kind: CallKind,
+    path: PathArg,
+    /// Second path (rename and link targets).
+    second: Option<PathArg>,
+}
+
+/// Syscalls decoded while learning, keyed by number for this architecture.
+fn traced_calls() -> HashMap<libc::c_long, TracedCall> {
+    use CallKind::*;
+    let table: &[(&str, CallKind, PathArg, Option<PathArg>)] = &[
+        ("open", Open, (None, 0), None),
+        ("openat", Open, (Some(0), 1), None),
+        ("openat2", OpenHow, (Some(0), 1), None),
+        ("creat", Creat, (None, 0), None),
+        ("execve", Exec, (None, 0), None),
+        ("execveat", Exec, (Some(0), 1), None),
+        ("mkdir", Create, (None, 0), None),
+        ("mkdirat", Create, (Some(0), 1), None),
+        ("mknod", Create, (None, 0), None),
+        ("mknodat", Create, (Some(0), 1), None),
+        ("symlink", Create, (None, 1), None),
+        ("symlinkat", Create, (Some(1), 2), None),
+        ("link", Create, (None, 1), None),
+        ("linkat", Create, (Some(2), 3), None),
+        ("unlink", Unlink, (None, 0), None),
+        ("unlinkat", UnlinkAt, (Some(0), 1), None),
+        ("rmdir", Rmdir, (None, 0), None),
+        ("rename", Rename, (None, 0), Some((None, 1))),
+        ("renameat", Rename, (Some(0), 1), Some((Some(2), 3))),
+        ("renameat2", Rename, (Some(0), 1), Some((Some(2), 3))),
+        ("truncate", Truncate, (None, 0), None),
+        ("ftruncate", Ftruncate, (None, 0), None),
+    ];
+    table
+        .iter()
+        .filter_map(|&(name, kind, path, second)| {
+            syscall_number(name).map(|nr| (nr, TracedCall { kind, path, second }))
+        })
+        .collect()
+}
+
+/// Syscall number, arguments and return value at a syscall stop.
+struct SyscallRegs {
+    nr: libc::c_long,
+    args: [u64; 6],
+    ret: i64,
+}
+
+fn get_regset<T>(pid: libc::pid_t) -> Option<T> {
+    // SAFETY: PTRACE_GETREGSET fills at most iov_len bytes of `regs`, a
+    // plain C struct for which all-zero is a valid value.
+    unsafe {
+        let mut regs: T = std::mem::zeroed();
+        let mut iov = libc::iovec {
+            iov_base: &mut regs as *mut T as *mut libc::c_void,
+            iov_len: std::mem::size_of::<T>(),
+        };
+        let rc = libc::ptrace(
+            libc::PTRACE_GETREGSET,
+            pid,
+            libc::NT_PRSTATUS as usize as *mut libc::c_void,
+            &mut iov as *mut libc::iovec as *mut libc::c_void,
+        );
+        (rc == 0).then_some(regs)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn syscall_regs(pid: libc::pid_t) -> Option<SyscallRegs> {
+    let r: libc::user_regs_struct = get_regset(pid)?;
+    Some(SyscallRegs {
+        nr: r.orig_rax as libc::c_long,
+        args: [r.rdi, r.rsi, r.rdx, r.r10, r.r8, r.r9],
+        ret: r.rax as i64,
+    })
+}
+
+#[cfg(target_arch = "aarch64")]
+fn syscall_regs(pid: libc::pid_t) -> Option<SyscallRegs> {
+    let r: libc::user_regs_struct = get_regset(pid)?;
+    Some(SyscallRegs {
+        nr: r.regs[8] as libc::c_long,
+        args: [
+            r.regs[0], r.regs[1], r.regs[2], r.regs[3], r.regs[4], r.regs[5],
+        ],
+        ret: r.regs[0] as i64,
+    })
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn syscall_regs(_pid: libc::pid_t) -> Option<SyscallRegs> {
+    None
+}
+
+/// Read `len` bytes at `addr` in the tracee, stopping early at unmapped pages.
+fn read_tracee(pid: libc::pid_t, addr: u64, len: usize) -> Vec<u8> {
+    use std::os::unix::fs::FileExt;
+    let Ok(mem) = fs::File::open(format!("/proc/{}/mem", pid)) else {
+        return Vec::new();
+    };
+    let mut out = vec![0u8; len];
+    let n = mem.read_at(&mut out, addr).unwrap_or(0);
+    out.truncate(n);
+    out
+}
+
+/// NUL-terminated string at `addr` in the tracee, read page by page.
+fn read_tracee_string(pid: libc::pid_t, addr: u64) -> Option<String> {
+    if addr == 0 {
+        return None;
+    }
+    let mut out = Vec::new();
+    let mut at = addr;
+    while out.len() < libc::PATH_MAX as usize {
+        let chunk = read_tracee(pid, at, (4096 - (at % 4096)) as usize);
+        if chunk.is_empty() {
+            return None;
+        }
+        if let Some(end) = chunk.iter().position(|b| *b == 0) {
+            out.extend_from_slice(&chunk[..end]);
+            return Some(String::from_utf8_lossy(&out).into_owned());
+        }
+        at += chunk.len() as u64;
+        out.extend_from_slice(&chunk);
+    }
+    None
+}
+
+/// Absolute form of `path` as seen by the tracee, relative to `dirfd` (or
+/// its cwd), with symlinks in existing parts resolved.
+fn resolve_tracee_path(pid: libc::pid_t, dirfd: Option<i64>, path: &str) -> Option<PathBuf> {
+    let base = match dirfd {
+        _ if path.starts_with('/') => PathBuf::from("/"),
+        Some(fd) if fd as i32 != libc::AT_FDCWD => {
+            fs::read_link(format!("/proc/{}/fd/{}", pid, fd as i32)).ok()?
+        }
+        _ => fs::read_link(format!("/proc/{}/cwd", pid)).ok()?,
+    };
+    let mut joined = PathBuf::new();
+    for part in base.join(path).components() {
+        match part {
+            std::path::Component::ParentDir => {
+                joined.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => joined.push(other),
+        }
+    }
+    if let Ok(real) = fs::canonicalize(&joined) {
+        return Some(real);
+    }
+    // Not there (yet, or any more): resolve the parent only.
+    let name = joined.file_name()?.to_owned();
+    let parent =
+        fs::canonicalize(joined.parent()?).unwrap_or_else(|_| joined.parent().unwrap().into());
+    Some(parent.join(name))
+}
+
+/// State of a syscall between its entry and exit stops.
+struct PendingCall {
+    call: TracedCall,
+    paths: [Option<PathBuf>; 2],
+    flags: u64,
+    /// Whether the first path existed and was a directory at entry.
+    existed: bool,
+    was_dir: bool,
+}
+
+/// A traced task; syscall entry and exit stops look alike and alternate.
+#[derive(Default)]
+struct TracedTask {
+    in_syscall: bool,
+    /// Decoded at entry when the syscall is one of `traced_calls`.
+    pending: Option<PendingCall>,
+}
+
+struct Learner {
+    calls: HashMap<libc::c_long, TracedCall>,
+    tasks: HashMap<libc::pid_t, TracedTask>,
+    accesses: Vec<LearnedAccess>,
+    seen: std::collections::HashSet<LearnedAccess>,
+}
+
+impl Learner {
+    fn record(&mut self, kind: LearnKind, path: PathBuf, dir: bool) {
+        let access = LearnedAccess { kind, path, dir };
+        if self.seen.insert(access.clone()) {
+            debug!("learn: {} {}", kind.name(), access.path.display());
+            self.accesses.push(access);
+        }
+    }
+
+    /// Executables mapped into a task right after exec: the program and
+    /// its ELF interpreter.
+    fn record_exec_maps(&mut self, pid: libc::pid_t) {
+        let Ok(maps) = fs::read_to_string(format!("/proc/{}/maps", pid)) else {
+            return;
+        };
+        for line in maps.lines() {
+            let mut fields = line.split_whitespace();
+            let perms = fields.nth(1).unwrap_or("");
+            if let Some(path) = fields.nth(3).filter(|p| p.starts_with('/')) {
+                if perms.contains('x') {
+                    self.record(LearnKind::Execute, PathBuf::from(path), false);
+                }
+            }
+        }
+    }
+
+    fn syscall_entry(&mut self, pid: libc::pid_t, regs: &SyscallRegs) -> Option<PendingCall> {
+        let call = *self.calls.get(&regs.nr)?;
+        let arg = |(dirfd, path): PathArg| {
+            let raw = read_tracee_string(pid, regs.args[path])?;
+            resolve_tracee_path(pid, dirfd.map(|i| regs.args[i] as i64), &raw)
+        };
+        let first = match call.kind {
+            CallKind::Ftruncate => {
+                fs::read_link(format!("/proc/{}/fd/{}", pid, regs.args[0] as i32)).ok()
+            }
+            _ => arg(call.path),
+        };
+        let flags = match call.kind {
+            CallKind::Open => regs.args[call.path.1 + 1],
+            CallKind::UnlinkAt => regs.args[2],
+            CallKind::OpenHow => {
+                let how = read_tracee(pid, regs.args[2], 8);
+                u64::from_ne_bytes(how.try_into().ok()?)
+            }
+            CallKind::Creat => (libc::O_CREAT | libc::O_WRONLY | libc::O_TRUNC) as u64,
+            _ => 0,
+        };
+        let meta = first.as_ref().and_then(|p| fs::symlink_metadata(p).ok());
+        Some(PendingCall {
+            call,
+            paths: [first, call.second.and_then(arg)],
+            flags,
+            existed: meta.is_some(),
+            was_dir: meta.is_some_and(|m| m.is_dir()),
+        })
+    }
+
+    fn syscall_exit(&mut self, pid: libc::pid_t, pending: PendingCall, ret: i64) {
+        if ret < 0 {
+            return;
+        }
+        let [first, second] = pending.paths;
+        let flags = pending.flags as i32;
+        match pending.call.kind {
+            CallKind::Open | CallKind::OpenHow | CallKind::Creat => {
+                if flags & libc::O_PATH != 0 {
+                    return;
+                }
+                // The descriptor names the file actually opened.
+                let Some(path) = fs::read_link(format!("/proc/{}/fd/{}", pid, ret))
+                    .ok()
+                    .filter(|p| p.is_absolute())
+                    .or(first)
+                else {
+                    return;
+                };
+                let dir = path.is_dir();
+                let mode = flags & libc::O_ACCMODE;
+                if flags & libc::O_CREAT != 0 && !pending.existed {
+                    self.record(LearnKind::Create, path.clone(), dir);
+                }
+                if mode == libc::O_RDONLY || mode == libc::O_RDWR {
+                    let kind = if dir {
+                        LearnKind::ReadDir
+                    } else {
+                        LearnKind::Read
+                    };
+                    self.record(kind, path.clone(), dir);
+                }
+                if mode == libc::O_WRONLY || mode == libc::O_RDWR {
+                    self.record(LearnKind::Write, path.clone(), dir);
+                    if flags & libc::O_TRUNC != 0 {
+                        self.record(LearnKind::Truncate, path, dir);
+                    }
+                }
+            }
+            CallKind::Exec => {
+                if let Some(path) = first {
+                    self.record(LearnKind::Execute, path, false);
+                }
+            }
+            CallKind::Create => {
+                if let Some(path) = first {
+                    let dir = path.is_dir();
+                    self.record(LearnKind::Create, path, dir);
+                }
+            }
+            CallKind::Unlink | CallKind::Rmdir | CallKind::UnlinkAt => {
+                let rmdir = pending.call.kind == CallKind::Rmdir || flags & libc::AT_REMOVEDIR != 0;
+                if let Some(path) = first {
+                    let kind = if rmdir {
+                        LearnKind::DeleteDir
+                    } else {
+                        LearnKind::Delete
+                    };
+                    self.record(kind, path, rmdir);
+                }
+            }
+            CallKind::Rename => {
+                if let Some(path) = first {
+                    self.record(LearnKind::Rename, path, pending.was_dir);
+                }
+                if let Some(path) = second {
+                    self.record(LearnKind::Create, path, pending.was_dir);
+                }
+            }
+            CallKind::Truncate | CallKind::Ftruncate => {
+                if let Some(path) = first {
+                    self.record(LearnKind::Truncate, path, false);
+                }
+            }
+        }
+    }
+
+    /// Drive all traced tasks until the last one exits; returns the exit
+    /// code of `child` (128 + signal number when it was killed).
+    fn trace(&mut self, child: libc::pid_t) -> Result<i32> {
+        let mut code = 1;
+        loop {
+            let mut status = 0;
+            // SAFETY: plain waitpid on our own tracees.
+            let pid = unsafe { libc::waitpid(-1, &mut status, libc::__WALL) };
+            if pid < 0 {
+                let err = std::io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::EINTR) => continue,
+                    Some(libc::ECHILD) => return Ok(code),
+                    _ => return Err(anyhow!("waitpid: {}", err)),
+                }
+            }
+            if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+                self.tasks.remove(&pid);
+                if pid == child {
+                    code = if libc::WIFEXITED(status) {
+                        libc::WEXITSTATUS(status)
+                    } else {
+                        128 + libc::WTERMSIG(status)
+                    };
+                }
+                continue;
+            }
+            if !libc::WIFSTOPPED(status) {
+                continue;
+            }
+            let sig = libc::WSTOPSIG(status);
+            let event = status >> 16;
+            let mut inject = 0;
+            // First stop of a task: the initial post-exec SIGTRAP of `child`,
+            // or the SIGSTOP of an auto-attached new task.
+            let first_stop = !self.tasks.contains_key(&pid);
+            if first_stop {
+                self.tasks.insert(pid, TracedTask::default());
+                if pid == child {
+                    Self::set_options(pid)?;
+                    self.record_exec_maps(pid);
+                } else if sig != libc::SIGSTOP {
+                    inject = sig;
+                }
+            } else if sig == libc::SIGTRAP | 0x80 {
+                let task = self.tasks.entry(pid).or_default();
+                let entering = !task.in_syscall;
+                task.in_syscall = entering;
+                let pending = task.pending.take();
+                if let Some(regs) = syscall_regs(pid) {
+                    if entering {
+                        let pending = self.syscall_entry(pid, &regs);
+                        self.tasks.entry(pid).or_default().pending = pending;
+                    } else if let Some(pending) = pending {
+                        self.syscall_exit(pid, pending, regs.ret);
+                    }
+                }
+            } else if sig == libc::SIGTRAP && event != 0 {
+                if event == libc::PTRACE_EVENT_EXEC {
+                    self.record_exec_maps(pid);
+                }
+            } else {
+                inject = sig;
+            }
+            // SAFETY: resumes a tracee stopped above.
+            unsafe {
+                libc::ptrace(
+                    libc::PTRACE_SYSCALL,
+                    pid,
+                    std::ptr::null_mut::<libc::c_void>(),
+                    inject as usize as *mut libc::c_void,
+                );
+            }
+        }
+    }
+
+    fn set_options(pid: libc::pid_t) -> Result<()> {
+        let options = libc::PTRACE_O_TRACESYSGOOD
+            | libc::PTRACE_O_TRACEEXEC
+            | libc::PTRACE_O_TRACEFORK
+            | libc::PTRACE_O_TRACEVFORK
+            | libc::PTRACE_O_TRACECLONE
+            | libc::PTRACE_O_EXITKILL;
+        // SAFETY: `pid` is our stopped tracee.
+        let rc = unsafe {
+            libc::ptrace(
+                libc::PTRACE_SETOPTIONS,
+                pid,
+                std::ptr::null_mut::<libc::c_void>(),
+                options as usize as *mut libc::c_void,
+            )
+        };
+        if rc != 0 {
+            return Err(anyhow!(
+                "ptrace(PTRACE_SETOPTIONS): {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Key a recorded access collapses to: a system prefix, a pseudo
+/// filesystem, or the first `depth` components of its directory.
+fn collapse_access(access: &LearnedAccess, depth: usize) -> (PathBuf, bool) {
+    for prefix in SYSTEM_PREFIXES {
+        if access.path.starts_with(prefix) {
+            return (PathBuf::from(prefix), true);
+        }
+    }
+    for prefix in PSEUDO_PREFIXES {
+        if access.path.starts_with(prefix) {
+            return (PathBuf::from(prefix), false);
+        }
+    }
+    // Listing is checked on the directory itself; everything else, including
+    // creating and removing directories, on the parent.
+    let dir = if access.kind == LearnKind::ReadDir {
+        access.path.as_path()
+    } else {
+        access.path.parent().unwrap_or(&access.path)
+    };
+    (dir.components().take(depth + 1).collect(), false)
+}
+
+/// Group name from a directory: `~/.cache` -> `cache`, `/` -> `root`.
+fn group_name_for(path: &std::path::Path) -> String {
+    let base = path
+        .file_name()
+        .map(|n| n.to_string_lossy().trim_start_matches('.').to_lowercase())
+        .unwrap_or_default();
+    let name: String = base
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.is_empty() {
+        "root".into()
+    } else {
+        name
+    }
+}
+
+fn permissions_from_access(set: BitFlags<AccessFs>) -> Permissions {
+    let flag = |a: AccessFs| Some(set.contains(a));
+    Permissions {
+        read_file: flag(AccessFs::ReadFile),
+        read_dir: flag(AccessFs::ReadDir),
+        execute: flag(AccessFs::Execute),
+        write_file: flag(AccessFs::WriteFile),
+        remove_file: flag(AccessFs::RemoveFile),
+        remove_dir: flag(AccessFs::RemoveDir),
+        truncate: flag(AccessFs::Truncate),
+    }
+}
+
+/// Collapse recorded accesses, each with the rights to grant for it, into
+/// access root groups: one `system` group for SYSTEM_PREFIXES, one group per
+/// remaining directory. Directories below another one that already has all
+/// their rights are dropped.
+fn learned_groups<'a>(
+    accesses: impl IntoIterator<Item = (&'a LearnedAccess, BitFlags<AccessFs>)>,
+    depth: usize,
+) -> HashMap<String, AccessRootGroup> {
+    let mut system = BitFlags::<AccessFs>::empty();
+    let mut system_paths: Vec<PathBuf> = Vec::new();
+    let mut dirs: BTreeMap<PathBuf, BitFlags<AccessFs>> = BTreeMap::new();
+    for (access, rights) in accesses {
+        let (key, is_system) = collapse_access(access, depth);
+        if is_system {
+            system.insert(rights);
+            if !system_paths.contains(&key) {
+                system_paths.push(key);
+            }
+        } else {
+            *dirs.entry(key).or_default() |= rights;
+        }
+    }
+    let covered = |path: &PathBuf, rights: BitFlags<AccessFs>| {
+        dirs.iter()
+            .any(|(other, r)| other != path && path.starts_with(other) && r.contains(rights))
+    };
+    let kept: Vec<(PathBuf, BitFlags<AccessFs>)> = dirs
+        .iter()
+        .filter(|(path, rights)| !covered(path, **rights))
+        .map(|(p, r)| (p.clone(), *r))
+        .collect();
+
+    let mut groups = HashMap::new();
+    if !system_paths.is_empty() {
+        system_paths.sort();
+        groups.insert(
+            "system".to_string(),
+            AccessRootGroup {
+                paths: system_paths
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect(),
+                permissions: permissions_from_access(system),
+            },
+        );
+    }
+    for (path, rights) in kept {
+        let base = group_name_for(&path);
+        let mut name = base.clone();
+        let mut n = 2;
+        while groups.contains_key(&name) {
+            name = format!("{}_{}", base, n);
+            n += 1;
+        }
+        groups.insert(
+            name,
+            AccessRootGroup {
+                paths: vec![path.display().to_string()],
+                permissions: permissions_from_access(rights),
+            },
+        );
+    }
+    groups
+}
+
+/// `learn -- <cmd>`: run the command unsandboxed under ptrace and emit a
+/// profile covering what it touched.
+fn learn_profile(args: &LearnArgs) -> Result<()> {
+    if SECCOMP_ARCH.is_none() {
+        return Err(anyhow!("learn is only supported on x86_64 and aarch64"));
+    }
+    let (bin, rest) = args
+        .command
+        .split_first()
+        .ok_or_else(|| anyhow!("no command specified (use: learn -- <CMD> [ARGS...])"))?;
+    let mut cmdp = Command::new(bin);
+    cmdp.args(rest);
+    // SAFETY: the closure only calls ptrace(2).
+    unsafe {
+        cmdp.pre_exec(|| {
+            if libc::ptrace(
+                libc::PTRACE_TRACEME,
+                0,
+                std::ptr::null_mut::<libc::c_void>(),
+                std::ptr::null_mut::<libc::c_void>(),
+            ) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = cmdp
+        .spawn()
+        .map_err(|e| anyhow!("failed to start {}: {}", bin, e))?;
+    // Ctrl-C stops the command, not the recording.
+    // SAFETY: only changes the launcher's dispositions, after the spawn.
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_IGN);
+        libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+    }
+    let mut learner = Learner {
+        calls: traced_calls(),
+        tasks: HashMap::new(),
+        accesses: Vec::new(),
+        seen: Default::default(),
+    };
+    let code = learner.trace(child.id() as libc::pid_t)?;
+    if code != 0 {
+        warn!(
+            "learn: command exited with {}; the profile may be incomplete",
+            code
+        );
+    }
+    info!("learn: recorded {} accesses", learner.accesses.len());
+
+    if let Some(path) = args.record.as_ref() {
+        let mut out = String::new();
+        for a in &learner.accesses {
+            out.push_str(&format!("{}\t{}\n", a.kind.name(), a.path.display()));
+        }
+        fs::write(path, out)?;
+    }
+
+    let name = args.name.clone().unwrap_or_else(|| {
+        PathBuf::from(bin)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "learned".into())
+    });
+    let accesses = learner.accesses.iter().map(|a| (a, a.rights()));
+    let access_roots = learned_groups(accesses, args.depth);
+    let handled = access_roots
+        .values()
+        .fold(BitFlags::<AccessFs>::empty(), |acc, g| {
+            acc | access_from_permissions(&g.permissions)
+        });
+    let handled = permissions_from_access(handled);
+    let profile = Profile {
+        description: Some(format!("Learned from: {}", args.command.join(" "))),
+        access_roots,
+        control_access: ControlAccess {
+            read_file: handled.read_file,
+            read_dir: handled.read_dir,
+            execute: handled.execute,
+            write_file: handled.write_file,
+            remove_file: handled.remove_file,
+            remove_dir: handled.remove_dir,
+            truncate: handled.truncate,
+        },
+        command: CommandSpec {
+            binary: bin.clone(),
+            args: rest.to_vec(),
+            working_dir: env::current_dir().ok().map(|d| d.display().to_string()),
+            ..CommandSpec::default()
+        },
+        ..Profile::default()
+    };
+    let cfg = Config {
+        version: Some(1),
+        profiles: HashMap::from([(name.clone(), profile)]),
+    };
+    let yaml = serde_yaml::to_string(&cfg)?;
+    if let Some(out) = args.output.as_ref() {
+        fs::write(out, &yaml)?;
+        println!("Profile '{}' written to {}", name, out.to_string_lossy());
+    } else {
+        println!("{}", yaml);
+    }
+    Ok(())
+}
+
+// ---------------- Strace import ----------------
+
+/// The accesses one syscall from an strace log needs; `denied` when it
+/// failed with EACCES or EPERM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StraceOp {
+    accesses: Vec<LearnedAccess>,
+    denied: bool,
+}
+
+/// Split off strace's `[pid N]` or `N` prefix and any timestamps; pid 0
+/// when the log has no pids.
+fn strip_strace_prefix(line: &str) -> (u32, &str) {
+    let mut pid = 0;
+    let mut rest = line.trim_start();
+    loop {
+        if let Some(tail) = rest.strip_prefix("[pid") {
+            let Some((n, tail)) = tail.split_once(']') else {
+                break;
+            };
+            pid = n.trim().parse().unwrap_or(0);
+            rest = tail.trim_start();
+            continue;
+        }
+        let (token, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+        if token.is_empty()
+            || !token
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == ':')
+        {
+            break;
+        }
+        if pid == 0 && token.chars().all(|c| c.is_ascii_digit()) {
+            pid = token.parse().unwrap_or(0);
+        }
+        rest = tail.trim_start();
+    }
+    (pid, rest)
+}
+
+/// Split a syscall argument list at top-level commas, keeping quoted
+/// strings, arrays, structs and `fd</path>` decorations intact.
+fn split_strace_args(s: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let (mut depth, mut quoted, mut escaped, mut start) = (0i32, false, false, 0);
+    for (i, c) in s.char_indices() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            '(' | '[' | '{' | '<' => depth += 1,
+            ')' | ']' | '}' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                out.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !s[start..].trim().is_empty() {
+        out.push(s[start..].trim());
+    }
+    out
+}
+
+/// Decode a quoted strace string with C, octal and `\x` escapes. None for
+/// anything else, including strings strace truncated (`"..."...`).
+fn strace_string(arg: &str) -> Option<String> {
+    let inner = arg.strip_prefix('"')?.strip_suffix('"')?;
+    let bytes = inner.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let (radix, from, max) = match bytes[i + 1] {
+            b'x' => (16, i + 2, 2),
+            b'0'..=b'7' => (8, i + 1, 3),
+            c => {
+                out.push(match c {
+                    b'n' => b'\n',
+                    b't' => b'\t',
+                    b'r' => b'\r',
+                    b'v' => 0x0b,
+                    b'f' => 0x0c,
+                    c => c,
+                });
+                i += 2;
+                continue;
+            }
+        };
+        let end = (from..bytes.len().min(from + max))
+            .find(|&j| !(bytes[j] as char).is_digit(radix))
+            .unwrap_or(bytes.len().min(from + max));
+        let code = std::str::from_utf8(&bytes[from..end])
+            .ok()
+            .and_then(|d| u8::from_str_radix(d, radix).ok())?;
+        out.push(code);
+        i = end;
+    }
+    Some(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Directory an `*at` call resolves against: the cwd for AT_FDCWD, the
+/// path `strace -y` prints for `3</dir>`, None for a bare descriptor.
+fn strace_dirfd(arg: &str, cwd: Option<&std::path::Path>) -> Option<PathBuf> {
+    if arg == "AT_FDCWD" {
+        return cwd.map(|c| c.to_path_buf());
+    }
+    let (_, path) = arg.split_once('<')?;
+    Some(PathBuf::from(path.strip_suffix('>')?))
+}
+
+/// Join `path` onto `base` and drop `.` and `..` lexically: the log comes
+/// from another machine, so nothing is resolved on disk.
+fn strace_path(base: Option<&std::path::Path>, path: &str) -> Option<PathBuf> {
+    let joined = if path.starts_with('/') {
+        PathBuf::from(path)
+    } else {
+        base?.join(path)
+    };
+    let mut out = PathBuf::new();
+    for c in joined.components() {
+        match c {
+            std::path::Component::ParentDir => {
+                out.pop();
+            }
+            std::path::Component::CurDir => {}
+            c => out.push(c),
+        }
+    }
+    Some(out).filter(|p| p.is_absolute())
+}
+
+/// Accesses an open(2) with these flags needs. O_CREAT counts as a
+/// creation, as the log does not show whether the file existed.
+fn open_accesses(path: PathBuf, flags: &str) -> Vec<LearnedAccess> {
+    let has = |f: &str| flags.split('|').any(|x| x.trim() == f);
+    let access = |kind, dir| LearnedAccess {
+        kind,
+        path: path.clone(),
+        dir,
+    };
+    if has("O_PATH") {
+        return Vec::new();
+    }
+    if has("O_DIRECTORY") {
+        return vec![access(LearnKind::ReadDir, true)];
+    }
+    let writable = has("O_WRONLY") || has("O_RDWR");
+    let mut out = Vec::new();
+    if !has("O_WRONLY") {
+        out.push(access(LearnKind::Read, false));
+    }
+    if writable {
+        out.push(access(LearnKind::Write, false));
+    }
+    if has("O_CREAT") {
+        out.push(access(LearnKind::Create, false));
+    }
+    if writable && has("O_TRUNC") {
+        out.push(access(LearnKind::Truncate, false));
+    }
+    out
+}
+
+/// Accesses a file syscall from the log needs: empty for calls Landlock
+/// does not restrict, None when a path cannot be resolved.
+fn strace_accesses(
+    name: &str,
+    args: &[&str],
+    cwd: Option<&std::path::Path>,
+) -> Option<Vec<LearnedAccess>> {
+    let path_at = |dirfd: Option<usize>, idx: usize| {
+        let path = strace_string(args.get(idx)?)?;
+        let base = match dirfd {
+            Some(d) => args.get(d).and_then(|a| strace_dirfd(a, cwd)),
+            None => cwd.map(|c| c.to_path_buf()),
+        };
+        strace_path(base.as_deref(), &path)
+    };
+    let one = |kind, path: Option<PathBuf>, dir| {
+        Some(vec![LearnedAccess {
+            kind,
+            path: path?,
+            dir,
+        }])
+    };
+    let flags = |idx: usize| {
+        let arg = args.get(idx).copied().unwrap_or("");
+        // openat2 passes `{flags=O_RDONLY|O_CLOEXEC, resolve=...}`.
+        let arg = arg.strip_prefix("{flags=").unwrap_or(arg);
+        arg.split([',', '}']).next().unwrap_or("")
+    };
+    match name {
+        "open" => Some(open_accesses(path_at(None, 0)?, flags(1))),
+        "openat" | "openat2" => Some(open_accesses(path_at(Some(0), 1)?, flags(2))),
+        "creat" => {
+            let path = path_at(None, 0)?;
+            Some(open_accesses(path, "O_WRONLY|O_CREAT|O_TRUNC"))
+        }
+        "execve" => one(LearnKind::Execute, path_at(None, 0), false),
+        "execveat" => one(LearnKind::Execute, path_at(Some(0), 1), false),
+        "mkdir" => one(LearnKind::Create, path_at(None, 0), true),
+        "mkdirat" => one(LearnKind::Create, path_at(Some(0), 1), true),
+        "mknod" => one(LearnKind::Create, path_at(None, 0), false),
+        "mknodat" => one(LearnKind::Create, path_at(Some(0), 1), false),
+        "unlink" => one(LearnKind::Delete, path_at(None, 0), false),
+        "rmdir" => one(LearnKind::DeleteDir, path_at(None, 0), true),
+        "unlinkat" if flags(2).contains("AT_REMOVEDIR") => {
+            one(LearnKind::DeleteDir, path_at(Some(0), 1), true)
+        }
+        "unlinkat" => one(LearnKind::Delete, path_at(Some(0), 1), false),
+        "truncate" => one(LearnKind::Truncate, path_at(None, 0), false),
+        "link" | "symlink" => one(LearnKind::Create, path_at(None, 1), false),
+        "linkat" => one(LearnKind::Create, path_at(Some(2), 3), false),
+        "symlinkat" => one(LearnKind::Create, path_at(Some(1), 2), false),
+        "rename" | "renameat" | "renameat2" => {
+            let (from, to) = if name == "rename" {
+                (path_at(None, 0)?, path_at(None, 1)?)
+            } else {
+                (path_at(Some(0), 1)?, path_at(Some(2), 3)?)
+            };
+            Some(vec![
+                LearnedAccess {
+                    kind: LearnKind::Rename,
+                    path: from,
+                    dir: false,
+                },
+                LearnedAccess {
+                    kind: LearnKind::Create,
+                    path: to,
+                    dir: false,
+                },
+            ])
+        }
+        _ => Some(Vec::new()),
+    }
+}
+
+/// Parse an `strace -f -e trace=file` log, with or without timestamps, pid
+/// prefixes and `-y` decorations. Unfinished calls are joined with their
+/// resumption; failures other than EACCES and EPERM are dropped. Also
+/// returns how many calls had a path that could not be resolved.
+fn parse_strace(text: &str, cwd: Option<&std::path::Path>) -> (Vec<StraceOp>, usize) {
+    let mut unfinished: HashMap<u32, String> = HashMap::new();
+    let mut cwds: HashMap<u32, PathBuf> = HashMap::new();
+    let mut ops = Vec::new();
+    let mut unresolved = 0;
+    for line in text.lines() {
+        let (pid, rest) = strip_strace_prefix(line);
+        let call = if let Some(tail) = rest.strip_prefix("<... ") {
+            let Some((_, tail)) = tail.split_once(" resumed>") else {
+                continue;
+            };
+            match unfinished.remove(&pid) {
+                Some(head) => head + tail,
+                None => continue,
+            }
+        } else if let Some(head) = rest.strip_suffix(" <unfinished ...>") {
+            unfinished.insert(pid, head.to_string());
+            continue;
+        } else {
+            rest.to_string()
+        };
+        // Signal and exit lines (`--- SIGCHLD`, `+++ exited`) have no name.
+        let Some((name, tail)) = call.split_once('(') else {
+            continue;
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            continue;
+        }
+        let Some((args, result)) = tail.rsplit_once(") = ") else {
+            continue;
+        };
+        let mut result = result.split_whitespace();
+        let denied = match result.next() {
+            Some("-1") if matches!(result.next(), Some("EACCES" | "EPERM")) => true,
+            Some("-1") | Some("?") | None => continue,
+            Some(_) => false,
+        };
+        let args = split_strace_args(args);
+        let base = cwds.get(&pid).map(|p| p.as_path()).or(cwd);
+        if name == "chdir" {
+            let dir = args.first().and_then(|a| strace_string(a));
+            match dir.and_then(|d| strace_path(base, &d)) {
+                Some(dir) if !denied => {
+                    cwds.insert(pid, dir);
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match strace_accesses(name, &args, base) {
+            Some(accesses) if accesses.is_empty() => {}
+            Some(accesses) => ops.push(StraceOp { accesses, denied }),
+            None => unresolved += 1,
+        }
+    }
+    (ops, unresolved)
+}
+
+/// `suggest --from-strace`: print the access root groups a profile needs
+/// for the operations in an strace log that it does not allow yet.
+fn suggest_from_strace(args: &SuggestArgs) -> Result<()> {
+    let profile = match (args.config.as_ref(), args.profile.as_ref()) {
+        (Some(cfg_path), Some(name)) => {
+            let cfg = load_config(cfg_path)?;
+            let profile = cfg
+                .profiles
+                .get(name)
+                .ok_or_else(|| anyhow!("profile '{}' not found in config", name))?;
+            Some(profile.clone())
+        }
+        (Some(_), None) => return Err(anyhow!("--profile is required when using --config")),
+        (None, Some(_)) => return Err(anyhow!("--config is required when using --profile")),
+        (None, None) => None,
+    };
+    let text = fs::read_to_string(&args.from_strace)
+        .map_err(|e| anyhow!("failed to read {}: {}", args.from_strace.display(), e))?;
+    let cwd = match args.cwd.clone() {
+        Some(dir) => Some(dir),
+        None => profile
+            .as_ref()
+            .and_then(|p| p.command.working_dir.as_deref())
+            .map(normalize_path)
+            .transpose()?
+            .map(PathBuf::from),
+    };
+    let (ops, unresolved) = parse_strace(&text, cwd.as_deref());
+    if unresolved > 0 {
+        warn!(
+            "suggest: skipped {} calls with paths that could not be resolved (pass --cwd, or trace with strace -y)",
+            unresolved
+        );
+    }
+    let plan = profile.as_ref().map(plan_ruleset_profile).transpose()?;
+
+    let mut needed: Vec<(&LearnedAccess, BitFlags<AccessFs>)> = Vec::new();
+    let (mut uncovered, mut unexplained) = (Vec::new(), Vec::new());
+    let mut seen = std::collections::HashSet::new();
+    let describe = |access: &LearnedAccess, denied: bool| {
+        let note = if denied { " (denied)" } else { "" };
+        format!("{} {}{}", access.kind.name(), access.path.display(), note)
+    };
+    for op in &ops {
+        let mut explained = false;
+        for access in &op.accesses {
+            // Landlock checks creating and removing entries on the parent.
+            let checked = match access.kind {
+                LearnKind::Create
+                | LearnKind::Rename
+                | LearnKind::Delete
+                | LearnKind::DeleteDir => access.path.parent().unwrap_or(&access.path),
+                _ => access.path.as_path(),
+            };
+            let missing = match plan.as_ref() {
+                Some(plan) => plan.denied_for(checked, access.rights()),
+                None => access.rights(),
+            };
+            if missing.is_empty() {
+                continue;
+            }
+            explained = true;
+            if seen.insert((access, op.denied)) {
+                needed.push((access, missing));
+                uncovered.push(describe(access, op.denied));
+            }
+        }
+        if op.denied && !explained {
+            let line: Vec<String> = op.accesses.iter().map(|a| describe(a, false)).collect();
+            let line = format!("{} (denied)", line.join(", "));
+            if !unexplained.contains(&line) {
+                unexplained.push(line);
+            }
+        }
+    }
+
+    let name = args.profile.clone().unwrap_or_else(|| "suggested".into());
+    if uncovered.is_empty() {
+        println!(
+            "# nothing to add: profile '{}' allows every operation in {}",
+            name,
+            args.from_strace.display()
+        );
+    } else {
+        println!("# not allowed by profile '{}':", name);
+        for line in &uncovered {
+            println!("#   {}", line);
+        }
+    }
+    if !unexplained.is_empty() {
+        println!("# denied although the profile allows them (DAC, an LSM or another sandbox):");
+        for line in &unexplained {
+            println!("#   {}", line);
+        }
+    }
+    if needed.is_empty() {
+        return Ok(());
+    }
+
+    // New groups only, named apart from the profile's own, so the patch
+    // merges into access_roots without widening existing groups.
+    let mut groups: Vec<(String, AccessRootGroup)> =
+        learned_groups(needed, args.depth).into_iter().collect();
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut access_roots: BTreeMap<String, AccessRootGroup> = BTreeMap::new();
+    for (base, group) in groups {
+        let taken = |n: &String| {
+            access_roots.contains_key(n)
+                || profile
+                    .as_ref()
+                    .is_some_and(|p| p.access_roots.contains_key(n))
+        };
+        let mut key = base.clone();
+        let mut n = 2;
+        while taken(&key) {
+            key = format!("{}_{}", base, n);
+            n += 1;
+        }
+        access_roots.insert(key, group);
+    }
+    let patch = BTreeMap::from([(
+        "profiles",
+        BTreeMap::from([(name, BTreeMap::from([("access_roots", access_roots)]))]),
+    )]);
+    print!("{}", serde_yaml::to_string(&patch)?);
+    Ok(())
+}
+
+// ---------------- Explain ----------------
+
+/// Rights by their profile names, in display order.
+const PERMISSION_NAMES: &[(&str, AccessFs)] = &[
+    ("read_file", AccessFs::ReadFile),
+    ("read_dir", AccessFs::ReadDir),
+    ("execute", AccessFs::Execute),
+    ("write_file", AccessFs::WriteFile),
+    ("remove_file", AccessFs::RemoveFile),
+    ("remove_dir", AccessFs::RemoveDir),
+    ("truncate", AccessFs::Truncate),
+];
+
+/// Parse `--access` names; all rights when the list is empty.
+fn parse_access_list(names: &[String]) -> Result<BitFlags<AccessFs>> {
+    if names.is_empty() {
+        return Ok(PERMISSION_NAMES.iter().map(|(_, a)| *a).collect());
+    }
+    let mut set = BitFlags::<AccessFs>::empty();
+    for name in names {
+        let (_, access) = PERMISSION_NAMES
+            .iter()
+            .find(|(n, _)| *n == name.trim())
+            .ok_or_else(|| {
+                let known: Vec<&str> = PERMISSION_NAMES.iter().map(|(n, _)| *n).collect();
+                anyhow!(
+                    "unknown access '{}' (expected one of: {})",
+                    name,
+                    known.join(", ")
+                )
+            })?;
+        set.insert(*access);
+    }
+    Ok(set)
+}
+
+/// Canonicalize the longest existing prefix of `path` and append the rest,
+/// so a path that does not exist yet still resolves through its parents.
+fn resolve_existing(path: &std::path::Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(real) = fs::canonicalize(existing) {
+            return rest.iter().rev().fold(real, |acc, name| acc.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// A Landlock rule with the group it came from.
+struct ExplainedRule<'a> {
+    group: &'a str,
+    path: PathBuf,
+    real: PathBuf,
+    access: BitFlags<AccessFs>,
+}
+
+/// `explain PATH`: which rules grant or deny each requested right on PATH.
+fn explain_path(args: &ExplainArgs) -> Result<()> {
+    let cfg = load_config(&args.config)?;
+    let profile = cfg
+        .profiles
+        .get(&args.profile)
+        .ok_or_else(|| anyhow!("profile '{}' not found in config", args.profile))?;
+    let requested = parse_access_list(&args.access)?;
+    let plan = plan_ruleset_profile(profile)?;
+    let protected = plan_protections(profile)?;
+
+    let mut group_names: Vec<&String> = profile.access_roots.keys().collect();
+    group_names.sort();
+    let mut rules = Vec::new();
+    for name in group_names {
+        let group = &profile.access_roots[name];
+        let allowed = group_access(profile, group);
+        let paths = group
+            .paths
+            .iter()
+            .map(|p| normalize_path(p))
+            .collect::<Result<Vec<_>>>()?;
+        for (path, access) in group_rules(&paths, allowed, &protected) {
+            rules.push(ExplainedRule {
+                group: name,
+                real: resolve_existing(&path),
+                path,
+                access,
+            });
+        }
+    }
+
+    let path = if args.path.is_absolute() {
+        args.path.clone()
+    } else {
+        env::current_dir()?.join(&args.path)
+    };
+    let real = resolve_existing(&path);
+    // Removing an entry is checked on its directory, without following the
+    // entry itself if it is a symlink.
+    let parent = path.parent().map(resolve_existing).unwrap_or(real.clone());
+
+    println!("Explain {} (profile '{}'):", path.display(), args.profile);
+    if real != path {
+        println!("  resolves to: {}", real.display());
+    }
+    for (name, right) in PERMISSION_NAMES {
+        if !requested.contains(*right) {
+            continue;
+        }
+        let on_parent = matches!(right, AccessFs::RemoveFile | AccessFs::RemoveDir);
+        let checked = if on_parent { &parent } else { &real };
+        let covering: Vec<&ExplainedRule> = rules
+            .iter()
+            .filter(|r| checked.starts_with(&r.real))
+            .collect();
+        let granting: Vec<&&ExplainedRule> = covering
+            .iter()
+            .filter(|r| r.access.contains(*right))
+            .collect();
+        let checked_on = if on_parent {
+            format!(" (checked on the parent, {})", checked.display())
+        } else {
+            String::new()
+        };
+        if !plan.handled.contains(*right) {
+            println!("  {}: allowed, not handled by this profile", name);
+            continue;
+        }
+        if !unsupported_names(BitFlags::from(*right)).is_empty() {
+            println!("  {}: allowed, ignored by this kernel's Landlock ABI", name);
+            continue;
+        }
+        if granting.is_empty() {
+            println!("  {}: denied{}", name, checked_on);
+            if covering.is_empty() {
+                println!("    no rule covers {} or its ancestors", checked.display());
+            }
+            for r in &covering {
+                println!(
+                    "    group '{}' at {} grants only {:?}",
+                    r.group,
+                    r.path.display(),
+                    access_names(r.access)
+                );
+            }
+        } else {
+            println!("  {}: allowed{}", name, checked_on);
+            for r in granting {
+                println!("    granted by group '{}' at {}", r.group, r.path.display());
+            }
+        }
+    }
+
+    let mut notes = Vec::new();
+    if let Ok(meta) = fs::symlink_metadata(&path) {
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(&path).unwrap_or_default();
+            notes.push(format!(
+                "{} is a symlink to {}: Landlock checks {}, so rules for the link's own location do not apply to it (removing the link is checked on {})",
+                path.display(),
+                target.display(),
+                real.display(),
+                parent.display()
+            ));
+        } else if real != path {
+            notes.push(format!(
+                "{} goes through symlinks; rules are matched against {}",
+                path.display(),
+                real.display()
+            ));
+        }
+    } else {
+        notes.push(format!(
+            "{} does not exist; it was resolved through its nearest existing ancestor",
+            path.display()
+        ));
+    }
+    for r in &rules {
+        if path.starts_with(&r.path) && !real.starts_with(&r.real) {
+            notes.push(format!(
+                "{} is below {} (group '{}') by name, but resolves outside it",
+                path.display(),
+                r.path.display(),
+                r.group
+            ));
+        } else if r.real != r.path && real.starts_with(&r.real) {
+            notes.push(format!(
+                "rule path {} (group '{}') resolves to {}",
+                r.path.display(),
+                r.group,
+                r.real.display()
+            ));
+        }
+    }
+    for t in &protected {
+        if real.starts_with(resolve_existing(&t.path)) {
+            notes.push(format!(
+                "{} is protected ({}): at most {:?}",
+                t.path.display(),
+                t.kind.name(),
+                access_names(t.allowed)
+            ));
+        }
+    }
+    if !notes.is_empty() {
+        println!("Notes:");
+        for note in notes {
+            println!("  - {}", note);
+        }
+    }
+    Ok(())
+}
+
+// ---------------- Mount checks ----------------
+
+/// Entry of /proc/self/mountinfo.
+struct MountInfo {
+    mount_point: PathBuf,
+    /// Root of the mount within its filesystem; not `/` for bind mounts.
+    root: String,
+    fs_type: String,
+    source: String,
+}
+
+impl MountInfo {
+    /// `findmnt` style: `/dev/sda1[/data]` when only a subtree is mounted.
+    fn describe_source(&self) -> String {
+        if self.root == "/" {
+            self.source.clone()
+        } else {
+            format!("{}[{}]", self.source, self.root)
+        }
+    }
+}
+
+/// Undo the octal escapes (`\040` for a space) mountinfo uses in paths.
+fn unescape_mountinfo(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let code = bytes.get(i + 1..i + 4).and_then(|d| {
+            std::str::from_utf8(d)
+                .ok()
+                .and_then(|d| u8::from_str_radix(d, 8).ok())
+        });
+        match code {
+            Some(b) if bytes[i] == b'\\' => {
+                out.push(b);
+                i += 4;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn parse_mountinfo(text: &str) -> Vec<MountInfo> {
+    text.lines()
+        .filter_map(|line| {
+            // Optional fields end at " - ", followed by fstype and source.
+            let (head, tail) = line.split_once(" - ")?;
+            let head: Vec<&str> = head.split(' ').collect();
+            let mut tail = tail.split(' ');
+            Some(MountInfo {
+                root: unescape_mountinfo(head.get(3)?),
+                mount_point: PathBuf::from(unescape_mountinfo(head.get(4)?)),
+                fs_type: tail.next()?.to_string(),
+                source: unescape_mountinfo(tail.next()?),
+            })
+        })
+        .collect()
+}
+
+/// A mount point strictly below one of the ruleset's paths: Landlock rules
+/// follow the mounted tree, which may come from anywhere.
+struct NestedMount {
+    mount: MountInfo,
+    under: PathBuf,
+    access: BitFlags<AccessFs>,
+}
+
+/// Mounts in `mountinfo` nested under rule paths of `plan`, skipping those
+/// at or below `expected` (created by the launcher itself). Overmounted
+/// points are reported once, with the topmost mount.
+fn nested_mounts(plan: &PlannedRuleset, mountinfo: &str, expected: &[PathBuf]) -> Vec<NestedMount> {
+    let rule_paths: Vec<PathBuf> = plan
+        .rules
+        .iter()
+        .map(|(p, _)| fs::canonicalize(p).unwrap_or_else(|_| p.clone()))
+        .collect();
+    let mut out: Vec<NestedMount> = Vec::new();
+    for mount in parse_mountinfo(mountinfo) {
+        if expected.iter().any(|e| mount.mount_point.starts_with(e)) {
+            continue;
+        }
+        let under = rule_paths
+            .iter()
+            .filter(|r| mount.mount_point != **r && mount.mount_point.starts_with(r))
+            .max_by_key(|r| r.components().count());
+        let Some(under) = under else {
+            continue;
+        };
+        out.retain(|n| n.mount.mount_point != mount.mount_point);
+        out.push(NestedMount {
+            access: plan.allowed_for(&mount.mount_point),
+            under: under.clone(),
+            mount,
+        });
+    }
+    out
+}
+
+fn current_nested_mounts(plan: &PlannedRuleset, expected: &[PathBuf]) -> Result<Vec<NestedMount>> {
+    let text = fs::read_to_string("/proc/self/mountinfo")?;
+    Ok(nested_mounts(plan, &text, expected))
+}
+
+impl NestedMount {
+    fn describe(&self) -> String {
+        format!(
+            "{}: {} from {}, nested under allowed path {}; granted {:?}",
+            self.mount.mount_point.display(),
+            self.mount.fs_type,
+            self.mount.describe_source(),
+            self.under.display(),
+            access_names(self.access)
+        )
+    }
+}
+
+fn print_nested_mounts(mounts: &[NestedMount]) {
+    if mounts.is_empty() {
+        return;
+    }
+    println!("Nested mounts:");
+    for m in mounts {
+        println!("  warning: {}", m.describe());
+    }
+}
+
+/// Log nested mounts; any of them is an error in strict mode.
+fn warn_nested_mounts(mounts: &[NestedMount], strict: bool) -> Result<()> {
+    for m in mounts {
+        warn!("mount check: {}", m.describe());
+    }
+    if strict && !mounts.is_empty() {
+        return Err(anyhow!(
+            "mount check: {} mount point(s) nested under allowed paths",
+            mounts.len()
+        ));
+    }
+    Ok(())
+}
+
+// ---------------- File descriptor hygiene ----------------
+
+/// All normalized paths of all access root groups.
+fn profile_paths(profile: &Profile) -> Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    for group in profile.access_roots.values() {
+        for p in &group.paths {
+            out.push(PathBuf::from(normalize_path(p)?));
+        }
+    }
+    Ok(out)
+}
+
+/// Close inherited descriptors above stderr except `keep`. Directory FDs
+/// outside every allowed path would let the command escape the ruleset via
+/// openat(); they are reported, and kept ones are an error in strict mode.
+fn close_inherited_fds(keep: &[i32], allowed: &[PathBuf], strict: bool) -> Result<()> {
+    let fds: Vec<i32> = fs::read_dir("/proc/self/fd")?
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().and_then(|n| n.parse().ok()))
+        .filter(|fd| *fd > 2)
+        .collect();
+    for fd in fds {
+        // SAFETY: F_GETFD only queries the descriptor; the read_dir FD is already closed.
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+            continue;
+        }
+        let target = fs::read_link(format!("/proc/self/fd/{}", fd)).ok();
+        let kept = keep.contains(&fd);
+        if let Some(path) = target.as_ref().filter(|t| t.is_dir()) {
+            if !allowed.iter().any(|a| path.starts_with(a)) {
+                if kept && strict {
+                    return Err(anyhow!(
+                        "kept fd {} refers to directory {} outside all allowed paths",
+                        fd,
+                        path.display()
+                    ));
+                }
+                warn!(
+                    "inherited fd {} refers to directory {} outside all allowed paths{}",
+                    fd,
+                    path.display(),
+                    if kept {
+                        " (kept by keep_fds)"
+                    } else {
+                        "; closing"
+                    }
+                );
+            }
+        }
+        if kept {
+            info!("keeping inherited fd {} ({:?})", fd, target);
+            continue;
+        }
+        // SAFETY: the descriptor was inherited and is not used by the launcher.
+        unsafe {
+            libc::close(fd);
+        }
+    }
+    Ok(())
+}
+
+// ---------------- Hooks ----------------
+
+impl Hooks {
+    /// Profiles named by hooks must exist and must not have hooks themselves.
+    fn validate(&self, cfg: &Config) -> Result<()> {
+        for hook in self.pre.iter().chain(&self.post) {
+            if let Hook::Sandboxed { profile, .. } = hook {
+                let target = cfg
+                    .profiles
+                    .get(profile)
+                    .ok_or_else(|| anyhow!("hook profile '{}' not found in config", profile))?;
+                if target.hooks.is_some() {
+                    return Err(anyhow!(
+                        "hook profile '{}' must not define hooks itself",
+                        profile
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Hook {
+    fn describe(&self) -> String {
+        match self {
+            Hook::Shell(run) => run.clone(),
+            Hook::Sandboxed { run, profile } => format!("{} (profile {})", run, profile),
+        }
+    }
+
+    fn redacted(&self) -> Hook {
+        match self {
+            Hook::Shell(run) => Hook::Shell(redact_text(run)),
+            Hook::Sandboxed { run, profile } => Hook::Sandboxed {
+                run: redact_text(run),
+                profile: profile.clone(),
+            },
+        }
+    }
+}
+
+/// Run one hook to completion. Sandboxed hooks re-invoke the launcher with
+/// their profile; `exit_code` is exported as EXIT_CODE for post hooks.
+fn run_hook(
+    hook: &Hook,
+    stage: &str,
+    config: Option<&PathBuf>,
+    exit_code: Option<&str>,
+    show_secrets: bool,
+) -> Result<()> {
+    let mut cmdp = match hook {
+        Hook::Shell(run) => {
+            let mut c = Command::new("/bin/sh");
+            c.arg("-c").arg(run);
+            c
+        }
+        Hook::Sandboxed { run, profile } => {
+            let config = config.ok_or_else(|| anyhow!("sandboxed hooks require --config"))?;
+            let mut c = Command::new(env::current_exe()?);
+            c.arg("--config")
+                .arg(config)
+                .arg("--profile")
+                .arg(profile)
+                .arg("--")
+                .arg("/bin/sh")
+                .arg("-c")
+                .arg(run);
+            c
+        }
+    };
+    if let Some(code) = exit_code {
+        cmdp.env("EXIT_CODE", code);
+    }
+    let shown = redact_unless(&hook.describe(), show_secrets);
+    info!("running {} hook: {}", stage, shown);
+    let status = cmdp.status()?;
+    if !status.success() {
+        return Err(anyhow!("{} hook failed ({}): {}", stage, status, shown));
+    }
+    Ok(())
+}
+
+/// Process forked before restrict_self that runs the post hooks, so they
+/// stay outside the sandbox. It waits for the exit code on a pipe; if the
+/// launcher fails before the command finishes, the hooks run with an
+/// empty EXIT_CODE.
+struct PostHooks {
+    pid: libc::pid_t,
+    notify: Option<fs::File>,
+}
+
+impl PostHooks {
+    fn spawn(hooks: Vec<Hook>, config: Option<PathBuf>, show_secrets: bool) -> Result<Self> {
+        let mut fds = [0 as libc::c_int; 2];
+        // SAFETY: pipe2 fills both descriptors, which the Files then own.
+        let (mut from, to) = unsafe {
+            if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            (fs::File::from_raw_fd(fds[0]), fs::File::from_raw_fd(fds[1]))
+        };
+        // SAFETY: the launcher is still single-threaded here, so the child
+        // may allocate and spawn processes.
+        match unsafe { libc::fork() } {
+            -1 => Err(std::io::Error::last_os_error().into()),
+            0 => {
+                drop(to);
+                // Keyboard signals reach the launcher's whole process group;
+                // the hooks must still run after Ctrl-C. Spawned hooks get an
+                // empty signal mask again.
+                // SAFETY: sigprocmask on this process only.
+                unsafe {
+                    let mut set: libc::sigset_t = std::mem::zeroed();
+                    libc::sigemptyset(&mut set);
+                    for sig in FORWARDED_SIGNALS {
+                        libc::sigaddset(&mut set, sig);
+                    }
+                    libc::sigprocmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+                }
+                let mut code = String::new();
+                let _ = from.read_to_string(&mut code);
+                for hook in &hooks {
+                    let code = Some(code.trim());
+                    if let Err(e) = run_hook(hook, "post", config.as_ref(), code, show_secrets) {
+                        warn!("{}", e);
+                    }
+                }
+                // SAFETY: leave without running the launcher's exit handlers.
+                unsafe { libc::_exit(0) }
+            }
+            pid => Ok(PostHooks {
+                pid,
+                notify: Some(to),
+            }),
+        }
+    }
+
+    /// Hand over the exit code and wait for the hooks to finish.
+    fn finish(mut self, code: i32) {
+        if let Some(mut notify) = self.notify.take() {
+            let _ = write!(notify, "{}", code);
+        }
+    }
+}
+
+impl Drop for PostHooks {
+    fn drop(&mut self) {
+        drop(self.notify.take());
+        // SAFETY: waits for our own child process.
+        unsafe {
+            libc::waitpid(self.pid, std::ptr::null_mut(), 0);
+        }
+    }
+}
+
+fn print_hooks(hooks: &Hooks, show_secrets: bool) {
+    if hooks.pre.is_empty() && hooks.post.is_empty() {
+        return;
+    }
+    println!("Hooks:");
+    for hook in &hooks.pre {
+        println!("  pre: {}", redact_unless(&hook.describe(), show_secrets));
+    }
+    for hook in &hooks.post {
+        println!("  post: {}", redact_unless(&hook.describe(), show_secrets));
+    }
+}
+
+// ---------------- Run report ----------------
+
+/// JSON summary written by `--report` once the command has exited.
+#[derive(Debug, Serialize)]
+struct RunReport {
+    #[serde(skip)]
+    file: fs::File,
+    profile: Option<String>,
+    config: Option<String>,
+    config_sha256: Option<String>,
+    command: Vec<String>,
+    kernel: String,
+    /// Landlock ABI version reported by the kernel (None when unavailable).
+    landlock_abi: Option<i32>,
+    /// None when Landlock was not applied.
+    restriction_status: Option<String>,
+    handled: Vec<&'static str>,
+    rules: Vec<ReportRule>,
+    started_at: String,
+    ended_at: String,
+    duration_ms: u128,
+    exit_code: i32,
+    signal: Option<i32>,
+    stopped: Option<String>,
+    rusage: ChildUsage,
+}
+
+#[derive(Debug, Serialize)]
+struct ReportRule {
+    path: String,
+    access: Vec<&'static str>,
+}
+
+/// Resource usage of waited-for children (getrusage RUSAGE_CHILDREN).
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+struct ChildUsage {
+    max_rss_kb: i64,
+    user_cpu_ms: i64,
+    system_cpu_ms: i64,
+}
+
+impl ChildUsage {
+    fn collect() -> Self {
+        // SAFETY: getrusage fills the zeroed struct.
+        let ru = unsafe {
+            let mut ru: libc::rusage = std::mem::zeroed();
+            libc::getrusage(libc::RUSAGE_CHILDREN, &mut ru);
+            ru
+        };
+        let ms = |tv: libc::timeval| tv.tv_sec * 1000 + tv.tv_usec / 1000;
+        ChildUsage {
+            max_rss_kb: ru.ru_maxrss,
+            user_cpu_ms: ms(ru.ru_utime),
+            system_cpu_ms: ms(ru.ru_stime),
+        }
+    }
+
+    /// CPU time accumulated since `before`. The kernel only keeps the
+    /// largest RSS of all waited-for children, which may include pre hooks.
+    fn since(self, before: ChildUsage) -> Self {
+        ChildUsage {
+            max_rss_kb: self.max_rss_kb,
+            user_cpu_ms: self.user_cpu_ms - before.user_cpu_ms,
+            system_cpu_ms: self.system_cpu_ms - before.system_cpu_ms,
+        }
+    }
+}
+
+impl RunReport {
+    fn create(path: &PathBuf, args: &Args, cmd: &[String]) -> Result<Self> {
+        let file = fs::File::create(path)
+            .map_err(|e| anyhow!("cannot create report {}: {}", path.display(), e))?;
+        let config_sha256 = match args.config.as_ref() {
+            Some(cfg) => {
+                let digest = Sha256::digest(fs::read(cfg)?);
+                Some(digest.iter().map(|b| format!("{:02x}", b)).collect())
+            }
+            None => None,
+        };
+        Ok(RunReport {
+            file,
+            profile: args.profile.clone(),
+            config: args.config.as_ref().map(|c| c.display().to_string()),
+            config_sha256,
+            command: cmd
+                .iter()
+                .map(|a| redact_unless(a, args.show_secrets))
+                .collect(),
+            kernel: fs::read_to_string("/proc/sys/kernel/osrelease")
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default(),
+            landlock_abi: landlock_abi_version(),
+            restriction_status: None,
+            handled: Vec::new(),
+            rules: Vec::new(),
+            started_at: String::new(),
+            ended_at: String::new(),
+            duration_ms: 0,
+            exit_code: 0,
+            signal: None,
+            stopped: None,
+            rusage: ChildUsage::default(),
+        })
+    }
+
+    fn set_rules(&mut self, plan: &PlannedRuleset) {
+        self.handled = access_names(plan.handled);
+        self.rules = plan
+            .rules
+            .iter()
+            .map(|(path, access)| ReportRule {
+                path: path.display().to_string(),
+                access: access_names(*access),
+            })
+            .collect();
+    }
+
+    fn write(mut self, outcome: &RunOutcome) -> Result<()> {
+        self.started_at = format_utc(outcome.started);
+        self.ended_at = format_utc(outcome.ended);
+        self.duration_ms = outcome
+            .ended
+            .duration_since(outcome.started)
+            .unwrap_or_default()
+            .as_millis();
+        self.exit_code = outcome.code;
+        self.signal = outcome.signal;
+        self.stopped = outcome.stopped.clone();
+        self.rusage = outcome.usage;
+        serde_json::to_writer_pretty(&self.file, &self)?;
+        writeln!(self.file)?;
+        Ok(())
+    }
+}
+
+/// ABI version of the running kernel's Landlock, if enabled.
+fn landlock_abi_version() -> Option<i32> {
+    // SAFETY: with LANDLOCK_CREATE_RULESET_VERSION (1) the syscall only
+    // returns the ABI version and creates nothing.
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<libc::c_void>(),
+            0usize,
+            1u32,
+        )
+    };
+    (abi > 0).then_some(abi as i32)
+}
+
+/// RFC 3339 UTC timestamp with milliseconds.
+fn format_utc(t: SystemTime) -> String {
+    let d = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = d.as_secs();
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        d.subsec_millis()
+    )
+}
+
+// ---------------- Namespaces ----------------
+
+impl Namespaces {
+    /// Configuration errors, reported before any hook or setup runs.
+    fn validate(&self, mounts: &[BindMount]) -> Result<()> {
+        let mount = self.mount.unwrap_or(false);
+        if !self.hide.is_empty() && !mount {
+            return Err(anyhow!("namespaces.hide requires namespaces.mount"));
+        }
+        if self.pid.unwrap_or(false) && !mount {
+            return Err(anyhow!("namespaces.pid requires namespaces.mount"));
+        }
+        if !mounts.is_empty() && !mount {
+            return Err(anyhow!("mounts require namespaces.mount"));
+        }
+        for path in &self.hide {
+            normalize_path(path)?;
+        }
+        for bind in mounts {
+            let source = normalize_path(&bind.source)?;
+            fs::metadata(&source).map_err(|e| anyhow!("mount source {}: {}", source, e))?;
+            normalize_path(&bind.target)?;
+        }
+        Ok(())
+    }
+}
+
+/// Unshare the configured namespaces in the launcher itself, so the command
+/// and the Landlock ruleset both see the new layout. Must run while the
+/// launcher is single-threaded (a requirement of CLONE_NEWUSER).
+fn enter_namespaces(ns: &Namespaces, network: NetworkMode, mounts: &[BindMount]) -> Result<()> {
+    let isolate_net = network != NetworkMode::Host;
+    // A network namespace is always created inside a user namespace.
+    let user = ns.user.unwrap_or(false) || isolate_net;
+    let mount = ns.mount.unwrap_or(false);
+    let pid = ns.pid.unwrap_or(false);
+    ns.validate(mounts)?;
+    let hide = ns
+        .hide
+        .iter()
+        .map(|p| normalize_path(p))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut flags = 0;
+    if user {
+        flags |= libc::CLONE_NEWUSER;
+    }
+    if mount {
+        flags |= libc::CLONE_NEWNS;
+    }
+    if isolate_net {
+        flags |= libc::CLONE_NEWNET;
+    }
+    if pid {
+        // Applies to children only: the next process spawned becomes PID 1.
+        flags |= libc::CLONE_NEWPID;
+    }
+    if flags == 0 {
+        return Ok(());
+    }
+    // SAFETY: getuid/getgid cannot fail.
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    // SAFETY: unshare only changes this process's namespaces.
+    if unsafe { libc::unshare(flags) } != 0 {
+        let err = std::io::Error::last_os_error();
+        return Err(anyhow!(
+            "cannot create namespaces: {} (check --check for unprivileged user namespaces{})",
+            err,
+            if user {
+                ""
+            } else {
+                "; without root a mount namespace needs namespaces.user"
+            }
+        ));
+    }
+    if user {
+        // setgroups must be denied before an unprivileged gid_map is written.
+        fs::write("/proc/self/setgroups", "deny")?;
+        fs::write("/proc/self/uid_map", format!("{} {} 1", uid, uid))?;
+        fs::write("/proc/self/gid_map", format!("{} {} 1", gid, gid))?;
+    }
+    let mut hidden = 0;
+    if mount {
+        // Keep our mounts from propagating back to the parent namespace.
+        mount_fs(None, "/", None, libc::MS_REC | libc::MS_PRIVATE, None)?;
+        // Sources are opened first, so they may lie under /tmp or a hidden path.
+        let sources = mounts
+            .iter()
+            .map(BindMount::open_source)
+            .collect::<Result<Vec<_>>>()?;
+        mount_tmpfs("/tmp", "mode=1777")?;
+        for path in &hide {
+            match fs::metadata(path) {
+                Ok(m) if m.is_dir() => mount_tmpfs(path, "mode=0700")?,
+                Ok(_) => mount_fs(Some("/dev/null"), path, None, libc::MS_BIND, None)?,
+                Err(_) => {
+                    info!("hide: {} does not exist; skipped", path);
+                    continue;
+                }
+            }
+            hidden += 1;
+        }
+        for (bind, source) in mounts.iter().zip(&sources) {
+            bind.apply(source)?;
+        }
+    }
+    if network == NetworkMode::Loopback {
+        bring_up_loopback()?;
+    }
+    info!(
+        "entered namespaces: user={}, mount={}, hidden={}, binds={}, network={}, pid={}",
+        user,
+        mount,
+        hidden,
+        mounts.len(),
+        network.name(),
+        pid
+    );
+    Ok(())
+}
+
+/// Set IFF_UP on `lo` in the current (new) network namespace.
+fn bring_up_loopback() -> Result<()> {
+    // SAFETY: a plain datagram socket used only for the interface ioctls,
+    // closed before returning; `ifreq` is zero-initialised.
+    unsafe {
+        let sock = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if sock < 0 {
+            return Err(anyhow!("socket: {}", std::io::Error::last_os_error()));
+        }
+        let mut req: libc::ifreq = std::mem::zeroed();
+        for (dst, src) in req.ifr_name.iter_mut().zip(b"lo") {
+            *dst = *src as libc::c_char;
+        }
+        let ok = libc::ioctl(sock, libc::SIOCGIFFLAGS, &mut req) == 0 && {
+            req.ifr_ifru.ifru_flags |= (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+            libc::ioctl(sock, libc::SIOCSIFFLAGS, &req) == 0
+        };
+        let err = std::io::Error::last_os_error();
+        libc::close(sock);
+        if !ok {
+            return Err(anyhow!("cannot bring up loopback: {}", err));
+        }
+    }
+    Ok(())
+}
+
+/// Whether an unprivileged process may create a user namespace, probed in
+/// a forked child (which drops to nobody first when running as root).
+fn probe_unprivileged_userns() -> bool {
+    // SAFETY: the child only makes raw syscalls and leaves with _exit.
+    unsafe {
+        match libc::fork() {
+            -1 => false,
+            0 => {
+                if libc::geteuid() == 0 && (libc::setgid(65534) != 0 || libc::setuid(65534) != 0) {
+                    libc::_exit(2);
+                }
+                libc::_exit(if libc::unshare(libc::CLONE_NEWUSER) == 0 {
+                    0
+                } else {
+                    1
+                });
+            }
+            pid => {
+                let mut status = 0;
+                libc::waitpid(pid, &mut status, 0) == pid
+                    && libc::WIFEXITED(status)
+                    && libc::WEXITSTATUS(status) == 0
+            }
+        }
+    }
+}
+
+/// First process of the PID namespace unshared by the launcher. As PID 1
+/// it mounts a /proc for the namespace, applies the deferred Landlock
+/// ruleset and forks: the fork continues to exec the command, PID 1 stays
+/// behind to reap orphans and exits with the command's status (128 + signal
+/// number when it was killed), which takes down the rest of the namespace.
+struct PidInit {
+    ruleset: Option<RulesetCreated>,
+}
+
+impl PidInit {
+    fn attach(self, cmdp: &mut Command) {
+        let mut ruleset = self.ruleset;
+        // SAFETY: runs between fork and exec while the launcher is still
+        // single-threaded; run_init never returns.
+        unsafe {
+            cmdp.pre_exec(move || {
+                if libc::unshare(libc::CLONE_NEWNS) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let proc_ = c"proc".as_ptr();
+                let target = c"/proc".as_ptr();
+                let flags = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+                if libc::mount(proc_, target, proc_, flags, std::ptr::null()) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if let Some(ruleset) = ruleset.take() {
+                    ruleset
+                        .restrict_self()
+                        .map_err(|e| std::io::Error::other(e.to_string()))?;
+                }
+                match libc::fork() {
+                    -1 => Err(std::io::Error::last_os_error()),
+                    0 => Ok(()),
+                    child => run_init(child),
+                }
+            });
+        }
+    }
+}
+
+fn run_init(child: libc::pid_t) -> ! {
+    // SAFETY: raw syscalls only; the process leaves through _exit.
+    unsafe {
+        // Dropping inherited descriptors includes std's exec-status pipe, so
+        // the launcher's spawn() returns once the command has exec'd.
+        libc::syscall(libc::SYS_close_range, 3u32, u32::MAX, 0u32);
+        for sig in FORWARDED_SIGNALS {
+            libc::signal(sig, libc::SIG_IGN);
+        }
+        loop {
+            let mut status = 0;
+            let pid = libc::waitpid(-1, &mut status, 0);
+            if pid == child {
+                let code = if libc::WIFEXITED(status) {
+                    libc::WEXITSTATUS(status)
+                } else if libc::WIFSIGNALED(status) {
+                    128 + libc::WTERMSIG(status)
+                } else {
+                    1
+                };
+                libc::_exit(code);
+            }
+            if pid < 0 && std::io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                libc::_exit(1);
+            }
+        }
+    }
+}
+
+/// Mount points the launcher creates itself in its mount namespace.
+fn launcher_mounts(profile: &Profile) -> Result<Vec<PathBuf>> {
+    let ns = profile.namespaces.clone().unwrap_or_default();
+    if !ns.mount.unwrap_or(false) {
+        return Ok(Vec::new());
+    }
+    let mut out = vec![PathBuf::from("/tmp")];
+    for path in ns
+        .hide
+        .iter()
+        .chain(profile.mounts.iter().map(|m| &m.target))
+    {
+        out.push(PathBuf::from(normalize_path(path)?));
+    }
+    Ok(out)
+}
+
+impl BindMount {
+    /// O_PATH handle on the source, taken before /tmp and hidden paths are
+    /// covered; closed again before restrict_self.
+    fn open_source(&self) -> Result<fs::File> {
+        let source = normalize_path(&self.source)?;
+        fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH)
+            .open(&source)
+            .map_err(|e| anyhow!("mount source {}: {}", source, e))
+    }
+
+    /// Bind `source` over the target (recursively), then remount read-only
+    /// if requested. The target must exist and be of the same type.
+    fn apply(&self, source: &fs::File) -> Result<()> {
+        let target = normalize_path(&self.target)?;
+        let target_meta =
+            fs::metadata(&target).map_err(|e| anyhow!("mount target {}: {}", target, e))?;
+        if source.metadata()?.is_dir() != target_meta.is_dir() {
+            return Err(anyhow!(
+                "mount {} -> {}: source and target must both be directories or both be files",
+                self.source,
+                target
+            ));
+        }
+        let fd_path = format!("/proc/self/fd/{}", source.as_raw_fd());
+        mount_fs(
+            Some(&fd_path),
+            &target,
+            None,
+            libc::MS_BIND | libc::MS_REC,
+            None,
+        )?;
+        if self.readonly.unwrap_or(false) {
+            // Flags locked by the outer mount namespace must be kept, or the
+            // remount fails with EPERM inside a user namespace.
+            let locked = mount_flags_of(&target)?;
+            mount_fs(
+                None,
+                &target,
+                None,
+                libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | locked,
+                None,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// MS_* flags of the mount containing `path`, as reported by statvfs.
+fn mount_flags_of(path: &str) -> Result<libc::c_ulong> {
+    let c_path = std::ffi::CString::new(path)?;
+    // SAFETY: statvfs writes into a zeroed struct we own.
+    let st = unsafe {
+        let mut st: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(c_path.as_ptr(), &mut st) != 0 {
+            return Err(anyhow!(
+                "statvfs {}: {}",
+                path,
+                std::io::Error::last_os_error()
+            ));
+        }
+        st
+    };
+    let pairs = [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ];
+    Ok(pairs
+        .iter()
+        .filter(|(st_flag, _)| st.f_flag & st_flag != 0)
+        .fold(0, |acc, (_, ms_flag)| acc | ms_flag))
+}
+
+fn mount_tmpfs(target: &str, data: &str) -> Result<()> {
+    mount_fs(
+        Some("tmpfs"),
+        target,
+        Some("tmpfs"),
+        libc::MS_NOSUID | libc::MS_NODEV,
+        Some(data),
+    )
+}
+
+fn mount_fs(
+    source: Option<&str>,
+    target: &str,
+    fstype: Option<&str>,
+    flags: libc::c_ulong,
+    data: Option<&str>,
+) -> Result<()> {
+    use std::ffi::CString;
+    let cstr = |s: Option<&str>| s.map(CString::new).transpose();
+    let (source, fstype, data) = (cstr(source)?, cstr(fstype)?, cstr(data)?);
+    let target_c = CString::new(target)?;
+    let ptr = |c: &Option<CString>| c.as_ref().map_or(std::ptr::null(), |c| c.as_ptr());
+    // SAFETY: all pointers are NUL-terminated strings or null, alive for the call.
+    let rc = unsafe {
+        libc::mount(
+            ptr(&source),
+            target_c.as_ptr(),
+            ptr(&fstype),
+            flags,
+            ptr(&data) as *const libc::c_void,
+        )
+    };
+    if rc != 0 {
+        return Err(anyhow!(
+            "mount on {} failed: {}",
+            target,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+fn print_namespaces(ns: &Namespaces, network: NetworkMode, mounts: &[BindMount]) -> Result<()> {
+    let user = ns.user.unwrap_or(false) || network != NetworkMode::Host;
+    if !user && !ns.mount.unwrap_or(false) {
+        return Ok(());
+    }
+    println!("Namespaces:");
+    println!("  user = {}", user);
+    println!("  mount = {}", ns.mount.unwrap_or(false));
+    if ns.mount.unwrap_or(false) {
+        println!("  /tmp = empty tmpfs");
+    }
+    for path in &ns.hide {
+        println!("  hide = {}", normalize_path(path)?);
+    }
+    for bind in mounts {
+        println!(
+            "  bind = {} -> {}{}",
+            normalize_path(&bind.source)?,
+            normalize_path(&bind.target)?,
+            if bind.readonly.unwrap_or(false) {
+                " (read-only)"
+            } else {
+                ""
+            }
+        );
+    }
+    println!("  network = {}", network.name());
+    if ns.pid.unwrap_or(false) {
+        println!("  pid = true (command runs under a minimal init, /proc remounted)");
+    }
+    Ok(())
+}
+
+// ---------------- Private tmpdir ----------------
+
+/// Per-run temporary directory (mode 0700), granted full rights in the
+/// ruleset and removed on drop.
+#[derive(Debug)]
+struct PrivateTmp {
+    path: PathBuf,
+}
+
+fn private_tmp_base() -> String {
+    env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|d| !d.is_empty() && std::path::Path::new(d).is_dir())
+        .unwrap_or_else(|| "/tmp".to_string())
+}
+
+impl PrivateTmp {
+    fn create() -> Result<Self> {
+        let template = format!("{}/ai-sandbox.XXXXXX", private_tmp_base());
+        let mut buf = std::ffi::CString::new(template)?.into_bytes_with_nul();
+        // SAFETY: mkdtemp rewrites the NUL-terminated template in place.
+        if unsafe { libc::mkdtemp(buf.as_mut_ptr() as *mut libc::c_char) }.is_null() {
+            return Err(anyhow!(
+                "cannot create private tmpdir: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        buf.pop();
+        let path = PathBuf::from(String::from_utf8(buf)?);
+        info!("private tmpdir: {}", path.display());
+        Ok(PrivateTmp { path })
+    }
+}
+
+impl Drop for PrivateTmp {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            warn!("failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+// ---------------- Stdio redirection ----------------
+
+#[derive(Debug, Default)]
+enum OpenedTarget {
+    #[default]
+    Inherit,
+    Null,
+    File(fs::File),
+}
+
+#[derive(Debug, Default)]
+struct OpenedStdio {
+    stdin: OpenedTarget,
+    stdout: OpenedTarget,
+    stderr: OpenedTarget,
+    tee: Option<fs::File>,
+}
+
+#[derive(Clone, Copy)]
+enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OpenedTarget {
+    /// `None` keeps the command's default (inherited) descriptor.
+    fn to_stdio(&self) -> Result<Option<Stdio>> {
+        Ok(match self {
+            OpenedTarget::Inherit => None,
+            OpenedTarget::Null => Some(Stdio::null()),
+            OpenedTarget::File(f) => Some(Stdio::from(f.try_clone()?)),
+        })
+    }
+
+    /// Where relayed output goes when the command writes into a pipe.
+    fn writer(&self, stream: OutputStream) -> Result<Sink> {
+        Ok(match (self, stream) {
+            (OpenedTarget::Inherit, OutputStream::Stdout) => Box::new(std::io::stdout()),
+            (OpenedTarget::Inherit, OutputStream::Stderr) => Box::new(std::io::stderr()),
+            (OpenedTarget::Null, _) => Box::new(std::io::sink()),
+            (OpenedTarget::File(f), _) => Box::new(f.try_clone()?),
+        })
+    }
+}
+
+/// Open redirection files in the launcher, before restrict_self, so they
+/// do not need to be covered by the ruleset.
+fn open_stdio(spec: &StdioSpec) -> Result<OpenedStdio> {
+    let open = |target: Option<&StdioTarget>, input: bool| -> Result<OpenedTarget> {
+        let mut opts = fs::OpenOptions::new();
+        let path = match target {
+            None | Some(StdioTarget::Mode(StdioMode::Inherit)) => return Ok(OpenedTarget::Inherit),
+            Some(StdioTarget::Mode(StdioMode::Null)) => return Ok(OpenedTarget::Null),
+            Some(StdioTarget::File { file }) if input => {
+                opts.read(true);
+                file
+            }
+            Some(StdioTarget::File { file }) => {
+                opts.write(true).create(true).truncate(true);
+                file
+            }
+            Some(StdioTarget::Append { append }) if input => {
+                return Err(anyhow!("stdio.stdin cannot use append: {}", append))
+            }
+            Some(StdioTarget::Append { append }) => {
+                opts.append(true).create(true);
+                append
+            }
+        };
+        let path = normalize_path(path)?;
+        let file = opts
+            .open(&path)
+            .map_err(|e| anyhow!("cannot open {}: {}", path, e))?;
+        Ok(OpenedTarget::File(file))
+    };
+    let tee = match spec.tee.as_ref() {
+        Some(t) => {
+            let path = normalize_path(t)?;
+            let file = fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&path)
+                .map_err(|e| anyhow!("cannot open {}: {}", path, e))?;
+            Some(file)
+        }
+        None => None,
+    };
+    Ok(OpenedStdio {
+        stdin: open(spec.stdin.as_ref(), true)?,
+        stdout: open(spec.stdout.as_ref(), false)?,
+        stderr: open(spec.stderr.as_ref(), false)?,
+        tee,
+    })
+}
+
+fn print_stdio(spec: Option<&StdioSpec>, tty: TtyMode) {
+    if tty != TtyMode::Never {
+        println!("Terminal:");
+        println!(
+            "  tty = {} (pty {})",
+            tty.name(),
+            if tty.wants_pty() {
+                "allocated"
+            } else {
+                "not allocated"
+            }
+        );
+    }
+    let spec = match spec {
+        Some(s) => s,
+        None => return,
+    };
+    let describe = |t: Option<&StdioTarget>| match t {
+        None | Some(StdioTarget::Mode(StdioMode::Inherit)) => "inherit".to_string(),
+        Some(StdioTarget::Mode(StdioMode::Null)) => "null".to_string(),
+        Some(StdioTarget::File { file }) => format!("file {}", file),
+        Some(StdioTarget::Append { append }) => format!("append {}", append),
+    };
+    println!("Stdio:");
+    println!("  stdin = {}", describe(spec.stdin.as_ref()));
+    println!("  stdout = {}", describe(spec.stdout.as_ref()));
+    println!("  stderr = {}", describe(spec.stderr.as_ref()));
+    if let Some(tee) = spec.tee.as_ref() {
+        println!("  tee = {}", tee);
+    }
+}
+
+// ---------------- Pseudo-terminal ----------------
+
+/// A pty pair. Both ends are opened before restrict_self, so the command
+/// needs no rule for /dev/ptmx or /dev/pts; Landlock (ABI <= 3) does not
+/// mediate ioctls on descriptors it already holds.
+#[derive(Debug)]
+struct Pty {
+    master: fs::File,
+    slave: fs::File,
+}
+
+/// pty master the window size is copied to (-1 when none).
+static PTY_MASTER: AtomicI32 = AtomicI32::new(-1);
+/// Launcher terminal the window size is copied from (-1 when none).
+static WINSIZE_SOURCE: AtomicI32 = AtomicI32::new(-1);
+
+fn open_pty() -> Result<Pty> {
+    let master = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open("/dev/ptmx")
+        .map_err(|e| anyhow!("cannot open /dev/ptmx: {}", e))?;
+    let mut name = [0 as libc::c_char; 64];
+    // SAFETY: the buffer outlives the calls and its length is passed along.
+    let path = unsafe {
+        if libc::grantpt(master.as_raw_fd()) != 0
+            || libc::unlockpt(master.as_raw_fd()) != 0
+            || libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len()) != 0
+        {
+            return Err(anyhow!(
+                "cannot set up pseudo-terminal: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        std::ffi::CStr::from_ptr(name.as_ptr())
+            .to_string_lossy()
+            .into_owned()
+    };
+    let slave = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(&path)
+        .map_err(|e| anyhow!("cannot open {}: {}", path, e))?;
+    info!("allocated pseudo-terminal {}", path);
+    Ok(Pty { master, slave })
+}
+
+impl Pty {
+    /// Use the slave as the command's stdio and controlling terminal.
+    fn attach(&self, cmdp: &mut Command) -> Result<()> {
+        cmdp.stdin(self.slave.try_clone()?)
+            .stdout(self.slave.try_clone()?)
+            .stderr(self.slave.try_clone()?);
+        // SAFETY: the closure only calls async-signal-safe functions. It runs
+        // after stdio is set up and after the hardening closures, which may
+        // already have called setsid().
+        unsafe {
+            cmdp.pre_exec(|| {
+                if libc::getsid(0) != libc::getpid() && libc::setsid() < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Copy the launcher's window size now and on every SIGWINCH, and put
+    /// the launcher's terminal (if any) into raw mode until the guard drops.
+    fn prepare_terminal(&self) -> Option<RawMode> {
+        PTY_MASTER.store(self.master.as_raw_fd(), Ordering::SeqCst);
+        // SAFETY: isatty only queries the descriptors.
+        let source = [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO]
+            .into_iter()
+            .find(|fd| unsafe { libc::isatty(*fd) == 1 });
+        if let Some(fd) = source {
+            WINSIZE_SOURCE.store(fd, Ordering::SeqCst);
+            // SAFETY: the handler only calls ioctl(2) on descriptors from atomics.
+            unsafe {
+                let mut sa: libc::sigaction = std::mem::zeroed();
+                sa.sa_sigaction = on_sigwinch as *const () as libc::sighandler_t;
+                sa.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut sa.sa_mask);
+                libc::sigaction(libc::SIGWINCH, &sa, std::ptr::null_mut());
+            }
+        }
+        sync_window_size();
+        if source == Some(libc::STDIN_FILENO) {
+            RawMode::enable(libc::STDIN_FILENO)
+        } else {
+            None
+        }
+    }
+}
+
+/// Async-signal-safe: copy the launcher's window size to the pty, or use
+/// 80x24 when the launcher has no terminal.
+fn sync_window_size() {
+    let master = PTY_MASTER.load(Ordering::SeqCst);
+    if master < 0 {
+        return;
+    }
+    let source = WINSIZE_SOURCE.load(Ordering::SeqCst);
+    // SAFETY: ioctl(2) with a properly sized winsize struct.
+    unsafe {
+        let mut ws: libc::winsize = std::mem::zeroed();
+        if source < 0 || libc::ioctl(source, libc::TIOCGWINSZ, &mut ws) != 0 || ws.ws_row == 0 {
+            ws.ws_row = 24;
+            ws.ws_col = 80;
+        }
+        libc::ioctl(master, libc::TIOCSWINSZ, &ws);
+    }
+}
+
+extern "C" fn on_sigwinch(_sig: libc::c_int) {
+    sync_window_size();
+}
+
+/// Saved terminal attributes, restored on drop.
+struct RawMode {
+    fd: RawFd,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enable(fd: RawFd) -> Option<Self> {
+        // SAFETY: tcgetattr/cfmakeraw/tcsetattr on a valid termios struct.
+        unsafe {
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut saved) != 0 {
+                return None;
+            }
+            let mut raw = saved;
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                return None;
+            }
+            Some(RawMode { fd, saved })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores attributes saved by `enable`.
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+/// Forward the launcher's stdin to the pty. At end of input, send the
+/// line discipline's EOF character (Ctrl-D; twice after a partial line, the
+/// first one only flushes it) so the command sees EOF too.
+fn spawn_stdin_relay(to: fs::File) {
+    // The master stays open for the launcher's lifetime: the SIGWINCH
+    // handler uses this descriptor, and closing it early hangs up the pty.
+    let mut to = std::mem::ManuallyDrop::new(to);
+    std::thread::spawn(move || {
+        let mut from = std::io::stdin();
+        let mut buf = [0u8; 4096];
+        let mut at_line_start = true;
+        loop {
+            match from.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if to.write_all(&buf[..n]).is_err() {
+                        return;
+                    }
+                    at_line_start = buf[n - 1] == b'\n';
+                }
+            }
+        }
+        let eof: &[u8] = if at_line_start { b"\x04" } else { b"\x04\x04" };
+        let _ = to.write_all(eof);
+    });
+}
+
+// ---------------- Process hardening ----------------
+
+/// Register prctl/setsid calls that run in the command's process right
+/// before exec. The kernel resets the dumpable flag on execve of a regular
+/// binary, so `dumpable: false` cannot keep the command from being traced:
+/// it marks the supervising launcher non-dumpable and disables core dumps
+/// (RLIMIT_CORE=0, which survives exec) for the command, and warns unless
+/// Yama already restricts ptrace.
+fn apply_hardening(cmdp: &mut Command, h: &Hardening, exec_mode: ExecMode) -> Result<()> {
+    let pdeathsig = h.pdeathsig.as_deref().map(parse_signal).transpose()?;
+    let new_session = h.new_session.unwrap_or(false);
+    let non_dumpable = h.dumpable == Some(false);
+    if pdeathsig.is_none() && !new_session && !non_dumpable {
+        return Ok(());
+    }
+
+    // Parent the command should outlive: the launcher when supervising, the
+    // launcher's own parent when exec replaces it.
+    // SAFETY: getpid/getppid cannot fail.
+    let expected_parent = unsafe {
+        match exec_mode {
+            ExecMode::Supervise => libc::getpid(),
+            ExecMode::Exec => libc::getppid(),
+        }
+    };
+    if non_dumpable && exec_mode == ExecMode::Supervise {
+        // SAFETY: prctl on the launcher itself.
+        unsafe {
+            libc::prctl(libc::PR_SET_DUMPABLE, 0, 0, 0, 0);
+        }
+    }
+    if non_dumpable && yama_ptrace_scope().unwrap_or(0) == 0 {
+        warn!(
+            "hardening.dumpable: false only disables core dumps; execve resets the dumpable flag, so other processes of the same user can still ptrace the command (set kernel.yama.ptrace_scope >= 1 to prevent that)"
+        );
+    }
+
+    // SAFETY: the closure only calls async-signal-safe functions.
+    unsafe {
+        cmdp.pre_exec(move || {
+            if new_session && libc::setsid() < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if let Some(sig) = pdeathsig {
+                if libc::prctl(libc::PR_SET_PDEATHSIG, sig as libc::c_ulong, 0, 0, 0) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // The parent may have died before PR_SET_PDEATHSIG took effect.
+                // (Inside a new PID namespace the parent is not visible: 0.)
+                let ppid = libc::getppid();
+                if ppid != expected_parent && ppid != 0 {
+                    libc::raise(sig);
+                }
+            }
+            if non_dumpable {
+                let no_core = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if libc::setrlimit(libc::RLIMIT_CORE, &no_core) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+
+    if h.close_tty_on_background.unwrap_or(false) && launcher_in_background() {
+        info!("launcher runs in the background; detaching terminal stdio from the command");
+        // SAFETY: isatty only queries the descriptors.
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 1 {
+                cmdp.stdin(Stdio::null());
+            }
+            if libc::isatty(libc::STDOUT_FILENO) == 1 {
+                cmdp.stdout(Stdio::null());
+            }
+            if libc::isatty(libc::STDERR_FILENO) == 1 {
+                cmdp.stderr(Stdio::null());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// True when the controlling terminal's foreground group is not ours.
+fn launcher_in_background() -> bool {
+    // SAFETY: plain queries on stdin and our own process group.
+    unsafe {
+        let fg = libc::tcgetpgrp(libc::STDIN_FILENO);
+        fg > 0 && fg != libc::getpgrp()
+    }
+}
+
+/// `kernel.yama.ptrace_scope`; None without Yama. From 1 on, only
+/// ancestors (or CAP_SYS_PTRACE) may attach, which survives exec.
+fn yama_ptrace_scope() -> Option<u32> {
+    fs::read_to_string("/proc/sys/kernel/yama/ptrace_scope")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn print_hardening(h: &Hardening) -> Result<()> {
+    let pdeathsig = h.pdeathsig.as_deref().map(parse_signal).transpose()?;
+    if h.dumpable.is_none()
+        && pdeathsig.is_none()
+        && h.new_session.is_none()
+        && h.close_tty_on_background.is_none()
+    {
+        return Ok(());
+    }
+    println!("Hardening:");
+    match h.dumpable {
+        Some(false) => {
+            let ptrace = match yama_ptrace_scope() {
+                Some(scope) if scope > 0 => format!("restricted by Yama ptrace_scope = {}", scope),
+                _ => "still allowed for processes of the same user".to_string(),
+            };
+            println!(
+                "  dumpable = false (core dumps disabled; execve resets the flag, ptrace of the command: {})",
+                ptrace
+            );
+        }
+        Some(true) => println!("  dumpable = true"),
+        None => {}
+    }
+    if let (Some(name), Some(sig)) = (h.pdeathsig.as_deref(), pdeathsig) {
+        println!("  pdeathsig = {} ({})", name, sig);
+    }
+    if let Some(n) = h.new_session {
+        println!("  new_session = {}", n);
+    }
+    if let Some(c) = h.close_tty_on_background {
+        println!("  close_tty_on_background = {}", c);
+    }
+    Ok(())
+}
+
+// ---------------- Capabilities ----------------
+
+/// Capability names without the `CAP_` prefix, indexed by number.
+const CAPABILITY_NAMES: &[&str] = &[
+    "chown",
+    "dac_override",
+    "dac_read_search",
+    "fowner",
+    "fsetid",
+    "kill",
+    "setgid",
+    "setuid",
+    "setpcap",
+    "linux_immutable",
+    "net_bind_service",
+    "net_broadcast",
+    "net_admin",
+    "net_raw",
+    "ipc_lock",
+    "ipc_owner",
+    "sys_module",
+    "sys_rawio",
+    "sys_chroot",
+    "sys_ptrace",
+    "sys_pacct",
+    "sys_admin",
+    "sys_boot",
+    "sys_nice",
+    "sys_resource",
+    "sys_time",
+    "sys_tty_config",
+    "mknod",
+    "lease",
+    "audit_write",
+    "audit_control",
+    "setfcap",
+    "mac_override",
+    "mac_admin",
+    "syslog",
+    "wake_alarm",
+    "block_suspend",
+    "audit_read",
+    "perfmon",
+    "bpf",
+    "checkpoint_restore",
+];
+
+const CAP_SETPCAP: u32 = 8;
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// Accepts `net_bind_service` as well as `CAP_NET_BIND_SERVICE`.
+fn parse_capability(name: &str) -> Result<u32> {
+    let lower = name.to_ascii_lowercase();
+    let short = lower.strip_prefix("cap_").unwrap_or(&lower);
+    CAPABILITY_NAMES
+        .iter()
+        .position(|n| *n == short)
+        .map(|i| i as u32)
+        .ok_or_else(|| anyhow!("unknown capability: {}", name))
+}
+
+fn capability_names(mask: u64) -> String {
+    let names: Vec<&str> = (0..CAPABILITY_NAMES.len())
+        .filter(|i| mask & (1 << i) != 0)
+        .map(|i| CAPABILITY_NAMES[i])
+        .collect();
+    if names.is_empty() {
+        "(none)".into()
+    } else {
+        names.join(", ")
+    }
+}
+
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// Effective and permitted sets of the launcher, as 64-bit masks.
+fn current_capabilities() -> Result<(u64, u64)> {
+    let mut header = CapHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let mut data = [CapData::default(); 2];
+    // SAFETY: capget fills two CapData entries for version 3.
+    if unsafe { libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) } != 0 {
+        return Err(anyhow!("capget: {}", std::io::Error::last_os_error()));
+    }
+    let join = |lo: u32, hi: u32| lo as u64 | (hi as u64) << 32;
+    Ok((
+        join(data[0].effective, data[1].effective),
+        join(data[0].permitted, data[1].permitted),
+    ))
+}
+
+impl Capabilities {
+    fn keep_mask(&self) -> Result<u64> {
+        self.keep
+            .iter()
+            .try_fold(0u64, |mask, name| Ok(mask | 1 << parse_capability(name)?))
+    }
+
+    /// Root (real or effective uid 0) is refused unless `allow_root` is set.
+    fn check_root(&self) -> Result<()> {
+        // SAFETY: getuid/geteuid cannot fail.
+        let root = unsafe { libc::getuid() == 0 || libc::geteuid() == 0 };
+        if root && !self.allow_root.unwrap_or(false) {
+            return Err(anyhow!(
+                "refusing to run as root; set capabilities.allow_root: true to allow it"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Capability sets applied to the command right before exec: the bounding
+/// set is cut down to `keep`, ambient and inheritable hold only `keep`.
+struct CapabilityDrop {
+    keep: u64,
+    last_cap: u32,
+    drop_bounding: bool,
+}
+
+impl CapabilityDrop {
+    fn prepare(spec: &Capabilities) -> Result<Self> {
+        let keep = spec.keep_mask()?;
+        let (effective, permitted) = current_capabilities()?;
+        if keep & !permitted != 0 {
+            warn!(
+                "capabilities.keep: {} not permitted to the launcher; cannot be kept",
+                capability_names(keep & !permitted)
+            );
+        }
+        let last_cap = fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(CAPABILITY_NAMES.len() as u32 - 1);
+        // Shrinking the bounding set needs CAP_SETPCAP. Without it no file
+        // capabilities apply anyway, as no_new_privs is set before exec.
+        let drop_bounding = effective & (1 << CAP_SETPCAP) != 0;
+        if !drop_bounding {
+            info!("no CAP_SETPCAP; capability bounding set left unchanged");
+        }
+        Ok(CapabilityDrop {
+            keep: keep & permitted,
+            last_cap,
+            drop_bounding,
+        })
+    }
+
+    /// Registered after the pid namespace init, which still needs
+    /// CAP_SYS_ADMIN to mount /proc.
+    fn attach(&self, cmdp: &mut Command) {
+        let CapabilityDrop {
+            keep,
+            last_cap,
+            drop_bounding,
+        } = *self;
+        // SAFETY: the closure only calls prctl(2) and capset(2).
+        unsafe {
+            cmdp.pre_exec(move || {
+                let kept = |cap: u32| keep & (1 << cap) != 0;
+                if drop_bounding {
+                    for cap in (0..=last_cap).filter(|&c| !kept(c)) {
+                        if libc::prctl(libc::PR_CAPBSET_DROP, cap as libc::c_ulong, 0, 0, 0) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                }
+                let clear_all = libc::PR_CAP_AMBIENT_CLEAR_ALL as libc::c_ulong;
+                if libc::prctl(libc::PR_CAP_AMBIENT, clear_all, 0, 0, 0) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let header = CapHeader {
+                    version: LINUX_CAPABILITY_VERSION_3,
+                    pid: 0,
+                };
+                let half = |bits: u32| CapData {
+                    effective: bits,
+                    permitted: bits,
+                    inheritable: bits,
+                };
+                let data = [half(keep as u32), half((keep >> 32) as u32)];
+                if libc::syscall(libc::SYS_capset, &header, data.as_ptr()) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // Ambient capabilities carry `keep` across exec for non-root.
+                let raise = libc::PR_CAP_AMBIENT_RAISE as libc::c_ulong;
+                for cap in (0..=last_cap).filter(|&c| kept(c)) {
+                    if libc::prctl(libc::PR_CAP_AMBIENT, raise, cap as libc::c_ulong, 0, 0) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+fn print_capabilities(spec: &Capabilities) -> Result<()> {
+    println!("Capabilities:");
+    println!("  keep = {}", capability_names(spec.keep_mask()?));
+    println!("  bounding, ambient and inheritable sets: everything else dropped");
+    println!("  allow_root = {}", spec.allow_root.unwrap_or(false));
+    Ok(())
+}
+
+// ---------------- Seccomp ----------------
+
+/// Denied by `seccomp.preset: default`: kernel interfaces outside Landlock's
+/// reach (other processes' memory, keyrings, BPF, perf, mounts, io_uring,
+/// module and kexec loading, swap, reboot).
+const SECCOMP_DEFAULT_DENY: &[&str] = &[
+    "ptrace",
+    "process_vm_readv",
+    "process_vm_writev",
+    "kcmp",
+    "keyctl",
+    "add_key",
+    "request_key",
+    "bpf",
+    "perf_event_open",
+    "userfaultfd",
+    "mount",
+    "umount2",
+    "pivot_root",
+    "move_mount",
+    "open_tree",
+    "fsopen",
+    "fsconfig",
+    "fsmount",
+    "fspick",
+    "mount_setattr",
+    "io_uring_setup",
+    "io_uring_enter",
+    "io_uring_register",
+    "kexec_load",
+    "kexec_file_load",
+    "init_module",
+    "finit_module",
+    "delete_module",
+    "swapon",
+    "swapoff",
+    "reboot",
+    "acct",
+    "iopl",
+    "ioperm",
+];
+
+/// Denied by `seccomp.preset: strict` on top of the default list.
+const SECCOMP_STRICT_DENY: &[&str] = &[
+    "unshare",
+    "setns",
+    "chroot",
+    "personality",
+    "syslog",
+    "name_to_handle_at",
+    "open_by_handle_at",
+    "quotactl",
+    "vhangup",
+    "modify_ldt",
+    "uselib",
+    "settimeofday",
+    "clock_settime",
+    "clock_adjtime",
+    "adjtimex",
+    "sethostname",
+    "setdomainname",
+    "lookup_dcookie",
+    "_sysctl",
+    "sysfs",
+    "ustat",
+];
+
+macro_rules! syscall_table {
+    ($($name:ident),* $(,)?) => {
+        &[$((stringify!($name), libc::$name)),*]
+    };
+}
+
+/// Syscalls with the same name on x86_64 and aarch64 (numbers differ).
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[rustfmt::skip]
+const SYSCALLS: &[(&str, libc::c_long)] = syscall_table!(
+    SYS_accept, SYS_accept4, SYS_acct, SYS_add_key, SYS_adjtimex, SYS_bind, SYS_bpf,
+    SYS_brk, SYS_capget, SYS_capset, SYS_chdir, SYS_chroot, SYS_clock_adjtime,
+    SYS_clock_getres, SYS_clock_gettime, SYS_clock_nanosleep, SYS_clock_settime, SYS_clone,
+    SYS_clone3, SYS_close, SYS_close_range, SYS_connect, SYS_copy_file_range,
+    SYS_delete_module, SYS_dup, SYS_dup3, SYS_epoll_create1, SYS_epoll_ctl, SYS_epoll_pwait,
+    SYS_epoll_pwait2, SYS_eventfd2, SYS_execve, SYS_execveat, SYS_exit, SYS_exit_group,
+    SYS_faccessat, SYS_faccessat2, SYS_fallocate, SYS_fanotify_init, SYS_fanotify_mark,
+    SYS_fchdir, SYS_fchmod, SYS_fchmodat, SYS_fchown, SYS_fchownat, SYS_fcntl,
+    SYS_fdatasync, SYS_fgetxattr, SYS_finit_module, SYS_flistxattr, SYS_flock,
+    SYS_fremovexattr, SYS_fsconfig, SYS_fsetxattr, SYS_fsmount, SYS_fsopen, SYS_fspick,
+    SYS_fstat, SYS_fstatfs, SYS_fsync, SYS_ftruncate, SYS_futex, SYS_futex_waitv,
+    SYS_get_mempolicy, SYS_get_robust_list, SYS_getcpu, SYS_getcwd, SYS_getdents64,
+    SYS_getegid, SYS_geteuid, SYS_getgid, SYS_getgroups, SYS_getitimer, SYS_getpeername,
+    SYS_getpgid, SYS_getpid, SYS_getppid, SYS_getpriority, SYS_getrandom, SYS_getresgid,
+    SYS_getresuid, SYS_getrusage, SYS_getsid, SYS_getsockname, SYS_getsockopt, SYS_gettid,
+    SYS_gettimeofday, SYS_getuid, SYS_getxattr, SYS_init_module, SYS_inotify_add_watch,
+    SYS_inotify_init1, SYS_inotify_rm_watch, SYS_io_cancel, SYS_io_destroy,
+    SYS_io_getevents, SYS_io_setup, SYS_io_submit, SYS_io_uring_enter,
+    SYS_io_uring_register, SYS_io_uring_setup, SYS_ioctl, SYS_ioprio_get, SYS_ioprio_set,
+    SYS_kcmp, SYS_kexec_load, SYS_keyctl, SYS_kill, SYS_landlock_add_rule,
+    SYS_landlock_create_ruleset, SYS_landlock_restrict_self, SYS_lgetxattr, SYS_linkat,
+    SYS_listen, SYS_listxattr, SYS_llistxattr, SYS_lookup_dcookie, SYS_lremovexattr,
+    SYS_lseek, SYS_lsetxattr, SYS_madvise, SYS_mbind, SYS_membarrier, SYS_memfd_create,
+    SYS_memfd_secret, SYS_migrate_pages, SYS_mincore, SYS_mkdirat, SYS_mknodat, SYS_mlock,
+    SYS_mlock2, SYS_mlockall, SYS_mmap, SYS_mount, SYS_mount_setattr, SYS_move_mount,
+    SYS_move_pages, SYS_mprotect, SYS_mq_getsetattr, SYS_mq_notify, SYS_mq_open,
+    SYS_mq_timedreceive, SYS_mq_timedsend, SYS_mq_unlink, SYS_mremap, SYS_mseal, SYS_msgctl,
+    SYS_msgget, SYS_msgrcv, SYS_msgsnd, SYS_msync, SYS_munlock, SYS_munlockall, SYS_munmap,
+    SYS_name_to_handle_at, SYS_nanosleep, SYS_newfstatat, SYS_nfsservctl,
+    SYS_open_by_handle_at, SYS_open_tree, SYS_openat, SYS_openat2, SYS_perf_event_open,
+    SYS_personality, SYS_pidfd_getfd, SYS_pidfd_open, SYS_pidfd_send_signal, SYS_pipe2,
+    SYS_pivot_root, SYS_pkey_alloc, SYS_pkey_free, SYS_pkey_mprotect, SYS_ppoll, SYS_prctl,
+    SYS_pread64, SYS_preadv, SYS_preadv2, SYS_prlimit64, SYS_process_madvise,
+    SYS_process_mrelease, SYS_process_vm_readv, SYS_process_vm_writev, SYS_pselect6,
+    SYS_ptrace, SYS_pwrite64, SYS_pwritev, SYS_pwritev2, SYS_quotactl, SYS_quotactl_fd,
+    SYS_read, SYS_readahead, SYS_readlinkat, SYS_readv, SYS_reboot, SYS_recvfrom,
+    SYS_recvmmsg, SYS_recvmsg, SYS_remap_file_pages, SYS_removexattr, SYS_renameat2,
+    SYS_request_key, SYS_restart_syscall, SYS_rseq, SYS_rt_sigaction, SYS_rt_sigpending,
+    SYS_rt_sigprocmask, SYS_rt_sigqueueinfo, SYS_rt_sigreturn, SYS_rt_sigsuspend,
+    SYS_rt_sigtimedwait, SYS_rt_tgsigqueueinfo, SYS_sched_get_priority_max,
+    SYS_sched_get_priority_min, SYS_sched_getaffinity, SYS_sched_getattr,
+    SYS_sched_getparam, SYS_sched_getscheduler, SYS_sched_rr_get_interval,
+    SYS_sched_setaffinity, SYS_sched_setattr, SYS_sched_setparam, SYS_sched_setscheduler,
+    SYS_sched_yield, SYS_seccomp, SYS_semctl, SYS_semget, SYS_semop, SYS_semtimedop,
+    SYS_sendmmsg, SYS_sendmsg, SYS_sendto, SYS_set_mempolicy, SYS_set_mempolicy_home_node,
+    SYS_set_robust_list, SYS_set_tid_address, SYS_setdomainname, SYS_setfsgid, SYS_setfsuid,
+    SYS_setgid, SYS_setgroups, SYS_sethostname, SYS_setitimer, SYS_setns, SYS_setpgid,
+    SYS_setpriority, SYS_setregid, SYS_setresgid, SYS_setresuid, SYS_setreuid, SYS_setsid,
+    SYS_setsockopt, SYS_settimeofday, SYS_setuid, SYS_setxattr, SYS_shmat, SYS_shmctl,
+    SYS_shmdt, SYS_shmget, SYS_shutdown, SYS_sigaltstack, SYS_signalfd4, SYS_socket,
+    SYS_socketpair, SYS_splice, SYS_statfs, SYS_statx, SYS_swapoff, SYS_swapon,
+    SYS_symlinkat, SYS_sync, SYS_syncfs, SYS_sysinfo, SYS_syslog, SYS_tee, SYS_tgkill,
+    SYS_timer_create, SYS_timer_delete, SYS_timer_getoverrun, SYS_timer_gettime,
+    SYS_timer_settime, SYS_timerfd_create, SYS_timerfd_gettime, SYS_timerfd_settime,
+    SYS_times, SYS_tkill, SYS_truncate, SYS_umask, SYS_umount2, SYS_uname, SYS_unlinkat,
+    SYS_unshare, SYS_userfaultfd, SYS_utimensat, SYS_vhangup, SYS_vmsplice, SYS_wait4,
+    SYS_waitid, SYS_write, SYS_writev,
+);
+
+/// Legacy syscalls that only x86_64 provides.
+#[cfg(target_arch = "x86_64")]
+#[rustfmt::skip]
+const ARCH_SYSCALLS: &[(&str, libc::c_long)] = syscall_table!(
+    SYS__sysctl, SYS_access, SYS_afs_syscall, SYS_alarm, SYS_arch_prctl, SYS_chmod,
+    SYS_chown, SYS_creat, SYS_dup2, SYS_epoll_create, SYS_epoll_ctl_old, SYS_epoll_wait,
+    SYS_epoll_wait_old, SYS_eventfd, SYS_fadvise64, SYS_fchmodat2, SYS_fork, SYS_futimesat,
+    SYS_get_thread_area, SYS_getdents, SYS_getpgrp, SYS_getpmsg, SYS_getrlimit,
+    SYS_inotify_init, SYS_ioperm, SYS_iopl, SYS_kexec_file_load, SYS_lchown, SYS_link,
+    SYS_lstat, SYS_mkdir, SYS_mknod, SYS_modify_ldt, SYS_open, SYS_pause, SYS_pipe,
+    SYS_poll, SYS_putpmsg, SYS_readlink, SYS_rename, SYS_renameat, SYS_rmdir, SYS_security,
+    SYS_select, SYS_sendfile, SYS_set_thread_area, SYS_setrlimit, SYS_signalfd, SYS_stat,
+    SYS_symlink, SYS_sync_file_range, SYS_sysfs, SYS_time, SYS_tuxcall, SYS_unlink,
+    SYS_uselib, SYS_ustat, SYS_utime, SYS_utimes, SYS_vfork, SYS_vserver,
+);
+
+#[cfg(target_arch = "aarch64")]
+const ARCH_SYSCALLS: &[(&str, libc::c_long)] = &[];
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const SYSCALLS: &[(&str, libc::c_long)] = &[];
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const ARCH_SYSCALLS: &[(&str, libc::c_long)] = &[];
+
+/// Name and AUDIT_ARCH_* value the filter checks `seccomp_data.arch` against.
+#[cfg(target_arch = "x86_64")]
+const SECCOMP_ARCH: Option<(&str, u32)> = Some(("x86_64", 0xc000_003e));
+
+#[cfg(target_arch = "aarch64")]
+const SECCOMP_ARCH: Option<(&str, u32)> = Some(("aarch64", 0xc000_00b7));
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const SECCOMP_ARCH: Option<(&str, u32)> = None;
+
+fn syscall_number(name: &str) -> Option<libc::c_long> {
+    SYSCALLS
+        .iter()
+        .chain(ARCH_SYSCALLS)
+        .find(|(n, _)| n.strip_prefix("SYS_") == Some(name))
+        .map(|&(_, nr)| nr)
+}
+
+impl SeccompSpec {
+    /// Resolved deny list: the preset minus `allow`, plus `deny`. Preset
+    /// entries missing on this architecture are skipped; unknown names in
+    /// `allow` or `deny` are errors.
+    fn denied(&self) -> Result<Vec<(String, libc::c_long)>> {
+        let arch = SECCOMP_ARCH
+            .map(|(name, _)| name)
+            .unwrap_or("this architecture");
+        for name in self.allow.iter().chain(&self.deny) {
+            if syscall_number(name).is_none() {
+                return Err(anyhow!("seccomp: unknown syscall on {}: {}", arch, name));
+            }
+        }
+        let preset: Vec<&str> = match self.preset {
+            SeccompPreset::None => Vec::new(),
+            SeccompPreset::Default => SECCOMP_DEFAULT_DENY.to_vec(),
+            SeccompPreset::Strict => [SECCOMP_DEFAULT_DENY, SECCOMP_STRICT_DENY].concat(),
+        };
+        let mut denied: Vec<(String, libc::c_long)> = Vec::new();
+        for name in preset
+            .into_iter()
+            .filter(|n| !self.allow.iter().any(|a| a == n))
+            .chain(self.deny.iter().map(String::as_str))
+        {
+            if let Some(nr) = syscall_number(name) {
+                if !denied.iter().any(|(_, d)| *d == nr) {
+                    denied.push((name.to_string(), nr));
+                }
+            }
+        }
+        Ok(denied)
+    }
+
+    fn ret_action(&self) -> u32 {
+        match self.action {
+            SeccompAction::Errno => {
+                libc::SECCOMP_RET_ERRNO | (libc::EPERM as u32 & libc::SECCOMP_RET_DATA)
+            }
+            SeccompAction::Kill => libc::SECCOMP_RET_KILL_PROCESS,
+        }
+    }
+}
+
+fn bpf_stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Classic BPF program for SECCOMP_MODE_FILTER, installed in the command
+/// right before exec (after Landlock's restrict_self).
+struct SeccompFilter {
+    program: Vec<libc::sock_filter>,
+}
+
+impl SeccompFilter {
+    /// `None` when nothing is denied.
+    fn compile(spec: &SeccompSpec) -> Result<Option<Self>> {
+        let denied = spec.denied()?;
+        if denied.is_empty() {
+            return Ok(None);
+        }
+        let (_, audit_arch) = SECCOMP_ARCH
+            .ok_or_else(|| anyhow!("seccomp filters are only supported on x86_64 and aarch64"))?;
+        let action = spec.ret_action();
+        let nr_offset = std::mem::offset_of!(libc::seccomp_data, nr) as u32;
+        let arch_offset = std::mem::offset_of!(libc::seccomp_data, arch) as u32;
+        let mut program = vec![
+            // Other ABIs (i386 via int 0x80, ...) have other numbers: kill.
+            bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, arch_offset),
+            bpf_jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                audit_arch,
+                1,
+                0,
+            ),
+            bpf_stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+            bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, nr_offset),
+        ];
+        if cfg!(target_arch = "x86_64") {
+            // x32 calls share AUDIT_ARCH_X86_64 but set __X32_SYSCALL_BIT.
+            program.push(bpf_jump(
+                libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+                0x4000_0000,
+                0,
+                1,
+            ));
+            program.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, action));
+        }
+        for (_, nr) in &denied {
+            program.push(bpf_jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                *nr as u32,
+                0,
+                1,
+            ));
+            program.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, action));
+        }
+        program.push(bpf_stmt(
+            libc::BPF_RET | libc::BPF_K,
+            libc::SECCOMP_RET_ALLOW,
+        ));
+        Ok(Some(SeccompFilter { program }))
+    }
+
+    /// Registered after every other pre_exec closure, so the launcher's own
+    /// setup (and the pid namespace init) is not filtered.
+    fn attach(&self, cmdp: &mut Command) {
+        let program = self.program.clone();
+        // SAFETY: the closure only calls prctl(2).
+        unsafe {
+            cmdp.pre_exec(move || {
+                let prog = libc::sock_fprog {
+                    len: program.len() as libc::c_ushort,
+                    filter: program.as_ptr() as *mut libc::sock_filter,
+                };
+                // Already set by restrict_self, unless Landlock is unavailable.
+                if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0
+                    || libc::prctl(
+                        libc::PR_SET_SECCOMP,
+                        libc::SECCOMP_MODE_FILTER,
+                        &prog as *const libc::sock_fprog,
+                    ) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+fn print_seccomp(spec: &SeccompSpec) -> Result<()> {
+    let denied = spec.denied()?;
+    println!("Seccomp:");
+    println!("  preset = {}", spec.preset.name());
+    println!("  action = {}", spec.action.name());
+    if denied.is_empty() {
+        println!("  deny = (none)");
+    } else {
+        let names: Vec<&str> = denied.iter().map(|(n, _)| n.as_str()).collect();
+        println!("  deny = {}", names.join(", "));
+    }
+    Ok(())
+}
+
+// ---------------- Environment policy ----------------
+
+/// Variables dropped from the inherited environment unless explicitly listed
+/// in `env_passthrough`. Matched against the upper-cased name.
+const SECRET_ENV_PATTERNS: &[&str] = &[
+    "TOKEN",
+    "*_TOKEN",
+    "*_TOKEN_*",
+    "*SECRET*",
+    "*PASSWORD*",
+    "*PASSWD*",
+    "*API_KEY*",
+    "*APIKEY*",
+    "*ACCESS_KEY*",
+    "*PRIVATE_KEY*",
+    "*CREDENTIAL*",
+    "AWS_*",
+    "AZURE_*",
+    "SSH_AUTH_SOCK",
+    "GPG_AGENT_INFO",
+];
+
+impl EnvPolicy {
+    fn name(self) -> &'static str {
+        match self {
+            EnvPolicy::Inherit => "inherit",
+            EnvPolicy::Clear => "clear",
+            EnvPolicy::Allowlist => "allowlist",
+        }
+    }
+}
+
+struct ResolvedEnv {
+    vars: BTreeMap<String, String>,
+    /// Inherited variables removed by `env_deny` or the secret denylist.
+    removed: Vec<String>,
+}
+
+fn resolve_env(
+    spec: Option<&CommandSpec>,
+    inherited: impl Iterator<Item = (String, String)>,
+) -> Result<ResolvedEnv> {
+    let policy = spec.and_then(|s| s.env_policy).unwrap_or_default();
+    let passthrough: &[String] = spec.map(|s| s.env_passthrough.as_slice()).unwrap_or(&[]);
+    let deny: &[String] = spec.map(|s| s.env_deny.as_slice()).unwrap_or(&[]);
+
+    let mut vars = BTreeMap::new();
+    let mut removed = Vec::new();
+    for (k, v) in inherited {
+        let passed = passthrough.iter().any(|p| glob_match(p, &k));
+        let base = match policy {
+            EnvPolicy::Inherit => true,
+            EnvPolicy::Clear => false,
+            EnvPolicy::Allowlist => passed,
+        };
+        if !base {
+            continue;
+        }
+        let denied = deny.iter().any(|p| glob_match(p, &k)) || (!passed && is_secret_env_name(&k));
+        if denied {
+            removed.push(k);
+        } else {
+            vars.insert(k, v);
+        }
+    }
+
+    if let Some(envs) = spec.and_then(|s| s.env.as_ref()) {
+        // Normalize env values that use ~/ expansion for better UX
+        for (k, v) in envs {
+            let nv = if v.starts_with("~/") {
+                normalize_path(v)?
+            } else {
+                v.clone()
+            };
+            vars.insert(k.clone(), nv);
+        }
+    }
+    Ok(ResolvedEnv { vars, removed })
+}
+
+fn is_secret_env_name(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    SECRET_ENV_PATTERNS.iter().any(|p| glob_match(p, &upper))
+}
+
+/// Shell-style match supporting `*` (any run) and `?` (one character).
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+fn print_environment(spec: Option<&CommandSpec>) -> Result<()> {
+    let policy = spec.and_then(|s| s.env_policy).unwrap_or_default();
+    let envs = resolve_env(spec, env::vars())?;
+    let keys: Vec<&String> = envs.vars.keys().collect();
+    println!("Environment (env_policy={}):", policy.name());
+    println!("  keys = {:?}", keys);
+    if !envs.removed.is_empty() {
+        println!("  removed = {:?}", envs.removed);
+    }
+    Ok(())
+}
+
+// ---------------- Secret redaction ----------------
+
+const REDACTED: &str = "<redacted>";
+
+/// Prefixes of well-known credential formats (GitHub, GitLab, OpenAI,
+/// Anthropic, Slack, AWS, Google).
+const SECRET_VALUE_PREFIXES: &[&str] = &[
+    "ghp_",
+    "gho_",
+    "ghu_",
+    "ghs_",
+    "ghr_",
+    "github_pat_",
+    "glpat-",
+    "sk-",
+    "xoxb-",
+    "xoxp-",
+    "xoxa-",
+    "AKIA",
+    "ASIA",
+    "AIza",
+];
+
+/// Copy of the profile that is safe to print: `command.env` values with
+/// secret-looking names, and token-looking words anywhere in `command` or
+/// the hook commands, are replaced with `<redacted>`.
+fn redact_profile(profile: &Profile) -> Profile {
+    let mut p = profile.clone();
+    if let Some(envs) = p.command.env.as_mut() {
+        for (k, v) in envs.iter_mut() {
+            *v = if is_secret_env_name(k) {
+                REDACTED.to_string()
+            } else {
+                redact_text(v)
+            };
+        }
+    }
+    p.command.binary = redact_text(&p.command.binary);
+    for a in p.command.args.iter_mut() {
+        *a = redact_text(a);
+    }
+    if let Some(hooks) = p.hooks.as_mut() {
+        for hook in hooks.pre.iter_mut().chain(hooks.post.iter_mut()) {
+            *hook = hook.redacted();
+        }
+    }
+    p
+}
+
+fn redact_unless(s: &str, show_secrets: bool) -> String {
+    if show_secrets {
+        s.to_string()
+    } else {
+        redact_text(s)
+    }
+}
+
+/// Replace token-looking words (runs of `[A-Za-z0-9_.-]`) in free text.
+fn redact_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        if looks_like_secret(word) {
+            out.push_str(REDACTED);
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    };
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut word, &mut out);
+    out
+}
+
+fn looks_like_secret(word: &str) -> bool {
+    if SECRET_VALUE_PREFIXES
+        .iter()
+        .any(|p| word.starts_with(p) && word.len() >= p.len() + 16)
+    {
+        return true;
+    }
+    // Long opaque strings mixing letters and digits (API keys, session ids).
+    word.len() >= 32
+        && word.chars().any(|c| c.is_ascii_digit())
+        && word.chars().any(|c| c.is_ascii_alphabetic())
+        && !word.contains('.')
+}
+
+// ---------------- Process supervision ----------------
+
+const FORWARDED_SIGNALS: [libc::c_int; 4] =
+    [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT];
+
+/// Process group of the supervised child (0 when none is running).
+static CHILD_PGID: AtomicI32 = AtomicI32::new(0);
+/// Signal received before the child was spawned, delivered right after.
+static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn forward_signal(sig: libc::c_int) {
+    let pgid = CHILD_PGID.load(Ordering::SeqCst);
+    if pgid > 0 {
+        // SAFETY: kill(2) is async-signal-safe.
+        unsafe {
+            libc::kill(-pgid, sig);
+        }
+    } else {
+        PENDING_SIGNAL.store(sig, Ordering::SeqCst);
+    }
+}
+
+fn install_forwarding_handlers() {
+    for sig in FORWARDED_SIGNALS {
+        // SAFETY: the handler only touches atomics and calls kill(2).
+        unsafe {
+            let mut sa: libc::sigaction = std::mem::zeroed();
+            sa.sa_sigaction = forward_signal as *const () as libc::sighandler_t;
+            sa.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut sa.sa_mask);
+            libc::sigaction(sig, &sa, std::ptr::null_mut());
+        }
+    }
+}
+
+fn set_child_pid(pid: libc::pid_t) {
+    CHILD_PGID.store(pid, Ordering::SeqCst);
+    if pid > 0 {
+        let pending = PENDING_SIGNAL.swap(0, Ordering::SeqCst);
+        if pending > 0 {
+            // SAFETY: plain kill(2) on the child's process group.
+            unsafe {
+                libc::kill(-pid, pending);
+            }
+        }
+    }
+}
+
+/// Job control for a child in its own process group on the launcher's
+/// terminal: the child's group owns the terminal while the launcher is the
+/// foreground job, and the terminal goes back to the launcher on drop.
+struct TerminalForeground {
+    launcher_pgrp: libc::pid_t,
+    /// Whether the child's group currently owns the terminal.
+    handed: Cell<bool>,
+}
+
+impl TerminalForeground {
+    /// None without a terminal on stdin. A launcher started in the
+    /// background keeps the terminal where it is until it is brought to
+    /// the foreground (see `suspend`).
+    fn acquire(child_pgid: libc::pid_t) -> Option<Self> {
+        // SAFETY: isatty/getpgrp only query process state.
+        let launcher_pgrp = unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return None;
+            }
+            libc::getpgrp()
+        };
+        let fg = TerminalForeground {
+            launcher_pgrp,
+            handed: Cell::new(false),
+        };
+        fg.hand_over(child_pgid);
+        Some(fg)
+    }
+
+    fn hand_over(&self, child_pgid: libc::pid_t) {
+        // SAFETY: tcgetpgrp only queries stdin.
+        if unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) } == self.launcher_pgrp {
+            set_terminal_pgrp(child_pgid);
+            self.handed.set(true);
+        }
+    }
+
+    /// The child's group was stopped: by Ctrl-Z (the child's group is the
+    /// foreground group), or by SIGTTIN/SIGTTOU when it used the terminal
+    /// while the launcher ran in the background. Take the terminal back
+    /// and stop the launcher with SIGTSTP, so the shell sees the job
+    /// stopped. Once continued, hand the terminal over again if the
+    /// launcher is now in the foreground (`fg`, not `bg`), and continue
+    /// the child's group.
+    fn suspend(&self, child_pgid: libc::pid_t) {
+        if self.handed.replace(false) {
+            set_terminal_pgrp(self.launcher_pgrp);
+        }
+        // SAFETY: plain libc calls on our own process and the child's group.
+        unsafe {
+            libc::raise(libc::SIGTSTP);
+        }
+        self.hand_over(child_pgid);
+        // SAFETY: see above.
+        unsafe {
+            libc::kill(-child_pgid, libc::SIGCONT);
+        }
+    }
+}
+
+impl Drop for TerminalForeground {
+    fn drop(&mut self) {
+        if self.handed.get() {
+            set_terminal_pgrp(self.launcher_pgrp);
+        }
+    }
+}
+
+fn set_terminal_pgrp(pgrp: libc::pid_t) {
+    // tcsetpgrp from a background group raises SIGTTOU; ignore it meanwhile.
+    // SAFETY: plain libc calls on stdin and our own signal disposition.
+    unsafe {
+        let prev = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        if libc::tcsetpgrp(libc::STDIN_FILENO, pgrp) != 0 {
+            warn!(
+                "failed to set terminal foreground group: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        libc::signal(libc::SIGTTOU, prev);
+    }
+}
+
+// ---------------- Landlock check ----------------
+
+fn perform_landlock_check() -> Result<String> {
+    // 1) Kernel version check (>= 5.13)
+    let osrelease = std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .unwrap_or_else(|_| String::from("unknown"));
+    let version_ok = parse_kernel_version_ge(&osrelease, 5, 13);
+
+    // 2) LSM list contains 'landlock'
+    let lsm_list = std::fs::read_to_string("/sys/kernel/security/lsm")
+        .unwrap_or_else(|_| String::from("<unavailable>"));
+    let lsm_ok = lsm_list.contains("landlock");
+
+    // 3) Optional: try to build minimal ruleset without applying
+    // We only build a report string; defer actual syscall-based check to later.
+
+    let mut report = String::new();
+    report.push_str(&format!("Kernel osrelease: {}\n", osrelease.trim()));
+    report.push_str(&format!("Kernel version >= 5.13: {}\n", yesno(version_ok)));
+    report.push_str(&format!("LSM list: {}\n", lsm_list.trim()));
+    report.push_str(&format!("Landlock listed in LSM: {}\n", yesno(lsm_ok)));
+
+    if version_ok && lsm_ok {
+        report.push_str("Result: Landlock appears available.\n");
+        Ok(report)
+    } else {
+        Err(anyhow!(
+            "Landlock not available: version_ok={} lsm_ok={}",
+            version_ok,
+            lsm_ok
+        ))
+    }
+}
+
+fn yesno(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn parse_kernel_version_ge(osrelease: &str, want_major: u32, want_minor: u32) -> bool {
+    // osrelease like "6.8.0-26-generic"
+    let parts: Vec<&str> = osrelease.split('.').collect();
+    if parts.len() < 2 {
+        return false;
+    }
+    let major = parts[0].parse::<u32>().unwrap_or(0);
+    let minor = parts[1].parse::<u32>().unwrap_or(0);
     (major > want_major) || (major == want_major && minor >= want_minor)
 }
 
@@ -737,7 +6494,7 @@ struct Config {
     profiles: HashMap<String, Profile>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 struct Profile {
     description: Option<String>,
     #[serde(default)]
@@ -747,6 +6504,206 @@ struct Profile {
     command: CommandSpec,
     log_level: Option<String>,
     dry_run: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    protect: Vec<Protect>,
+    /// Accept that `protect` takes rights from the directories leading to a
+    /// protected entry, such as the project root; without it such a run is
+    /// refused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allow_reduced_dirs: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hardening: Option<Hardening>,
+    /// Inherited file descriptors (above 2) kept open for the command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keep_fds: Vec<i32>,
+    /// Same as `--strict`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    strict: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hooks: Option<Hooks>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tmpdir: Option<TmpdirMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespaces: Option<Namespaces>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    network: Option<NetworkMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seccomp: Option<SeccompSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    capabilities: Option<Capabilities>,
+    /// Bind mounts applied in the private mount namespace (needs
+    /// `namespaces.mount`); the ruleset sees the layout they produce.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mounts: Vec<BindMount>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct BindMount {
+    source: String,
+    target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    readonly: Option<bool>,
+}
+
+/// `none`: a new network namespace without interfaces up; `loopback`: the
+/// same with `lo` up; `host` (default): the launcher's network.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum NetworkMode {
+    None,
+    Loopback,
+    #[default]
+    Host,
+}
+
+impl NetworkMode {
+    fn name(self) -> &'static str {
+        match self {
+            NetworkMode::None => "none",
+            NetworkMode::Loopback => "loopback",
+            NetworkMode::Host => "host",
+        }
+    }
+}
+
+/// Capabilities the command may keep; all others are dropped before exec.
+/// Only profiles with this section refuse root (see `allow_root`); without
+/// it, and in root mode, a root launcher passes its full capability set on
+/// and only logs a warning.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+struct Capabilities {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keep: Vec<String>,
+    /// Run even when the launcher is root (refused otherwise).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allow_root: Option<bool>,
+}
+
+/// Syscall filter for the command: a preset deny list, adjusted by `allow`
+/// and `deny`; denied calls fail with EPERM (`errno`) or kill the process.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+struct SeccompSpec {
+    #[serde(default)]
+    preset: SeccompPreset,
+    #[serde(default)]
+    action: SeccompAction,
+    /// Syscalls removed from the preset's deny list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allow: Vec<String>,
+    /// Syscalls denied in addition to the preset.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deny: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum SeccompPreset {
+    #[default]
+    Default,
+    Strict,
+    None,
+}
+
+impl SeccompPreset {
+    fn name(self) -> &'static str {
+        match self {
+            SeccompPreset::Default => "default",
+            SeccompPreset::Strict => "strict",
+            SeccompPreset::None => "none",
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum SeccompAction {
+    #[default]
+    Errno,
+    Kill,
+}
+
+impl SeccompAction {
+    fn name(self) -> &'static str {
+        match self {
+            SeccompAction::Errno => "errno (EPERM)",
+            SeccompAction::Kill => "kill",
+        }
+    }
+}
+
+/// Linux namespaces entered by the launcher before Landlock is applied.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+struct Namespaces {
+    /// Unprivileged user namespace mapping the caller's uid/gid to itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user: Option<bool>,
+    /// Private mount namespace with a fresh tmpfs on /tmp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mount: Option<bool>,
+    /// Paths that appear empty: a tmpfs over directories, /dev/null over files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hide: Vec<String>,
+    /// Fresh PID namespace: the command runs under a minimal init with a
+    /// matching /proc.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pid: Option<bool>,
+}
+
+/// `private`: a fresh per-run temporary directory exported as TMPDIR.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum TmpdirMode {
+    Private,
+}
+
+/// Shell commands run outside the sandbox before the command starts and
+/// after it exits (post hooks see `EXIT_CODE`).
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+struct Hooks {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pre: Vec<Hook>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    post: Vec<Hook>,
+}
+
+/// A `/bin/sh -c` command line, or `{run, profile}` to run it under another
+/// profile of the same config file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+enum Hook {
+    Shell(String),
+    Sandboxed { run: String, profile: String },
+}
+
+/// Process attributes set on the command right before exec.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+struct Hardening {
+    /// `false`: PR_SET_DUMPABLE 0 and no core dumps (see `apply_hardening`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dumpable: Option<bool>,
+    /// Signal delivered to the command when its parent dies (PR_SET_PDEATHSIG).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pdeathsig: Option<String>,
+    /// Start the command in a new session without a controlling terminal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    new_session: Option<bool>,
+    /// When the launcher runs in the background of its terminal, replace
+    /// terminal stdio of the command with /dev/null.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    close_tty_on_background: Option<bool>,
+}
+
+/// Well-known paths inside access roots that stay protected even when the
+/// surrounding group is writable.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Protect {
+    /// `.git` (directory or `gitdir:` file and the directory it points to): read-only.
+    Vcs,
+    /// `.env*` files: inaccessible.
+    Dotenv,
+    /// `.github/workflows`: read-only.
+    CiConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -791,7 +6748,7 @@ struct ControlAccess {
     truncate: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 struct CommandSpec {
     binary: String,
     #[serde(default)]
@@ -800,6 +6757,338 @@ struct CommandSpec {
     working_dir: Option<String>,
     #[serde(default)]
     env: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exec_mode: Option<ExecMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    env_policy: Option<EnvPolicy>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    env_passthrough: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    env_deny: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    limits: Option<Limits>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stdio: Option<StdioSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tty: Option<TtyMode>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+struct StdioSpec {
+    /// `null`, `inherit` (default) or `{file: PATH}`.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_stdio_target",
+        skip_serializing_if = "Option::is_none"
+    )]
+    stdin: Option<StdioTarget>,
+    /// `null`, `inherit` (default), `{file: PATH}` (truncate) or `{append: PATH}`.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_stdio_target",
+        skip_serializing_if = "Option::is_none"
+    )]
+    stdout: Option<StdioTarget>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_stdio_target",
+        skip_serializing_if = "Option::is_none"
+    )]
+    stderr: Option<StdioTarget>,
+    /// Append a copy of stdout and stderr to this file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tee: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+enum StdioTarget {
+    Mode(StdioMode),
+    File { file: String },
+    Append { append: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum StdioMode {
+    Null,
+    Inherit,
+}
+
+/// YAML `null` means /dev/null here, not "unset".
+fn deserialize_stdio_target<'de, D>(d: D) -> std::result::Result<Option<StdioTarget>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let target = Option::<StdioTarget>::deserialize(d)?;
+    Ok(Some(target.unwrap_or(StdioTarget::Mode(StdioMode::Null))))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+struct Limits {
+    /// Wall-clock limit in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+    /// Stop after this many seconds without stdout/stderr output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    idle_timeout: Option<u64>,
+    /// Signals sent to the process group when a limit expires (default: TERM, KILL).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    kill_signals: Vec<String>,
+    /// Seconds to wait between signals of `kill_signals` (default: 10).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kill_grace: Option<u64>,
+    /// Resource limits applied with setrlimit(2) right before exec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nofile: Option<RlimitSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nproc: Option<RlimitSpec>,
+    /// Address space (RLIMIT_AS), bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address_space: Option<RlimitSpec>,
+    /// CPU time, seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cpu: Option<RlimitSpec>,
+    /// Largest file the process may create, bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fsize: Option<RlimitSpec>,
+    /// Core dump size, bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    core: Option<RlimitSpec>,
+    /// Stack size, bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stack: Option<RlimitSpec>,
+}
+
+/// A single value sets both soft and hard limits; `{soft, hard}` sets them
+/// separately.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+enum RlimitSpec {
+    Value(u64),
+    Range { soft: u64, hard: u64 },
+}
+
+impl RlimitSpec {
+    fn soft_hard(self) -> (u64, u64) {
+        match self {
+            RlimitSpec::Value(v) => (v, v),
+            RlimitSpec::Range { soft, hard } => (soft, hard),
+        }
+    }
+}
+
+const DEFAULT_KILL_GRACE: u64 = 10;
+
+impl Limits {
+    fn rlimit_specs(&self) -> Vec<(&'static str, libc::c_int, RlimitSpec)> {
+        [
+            ("nofile", libc::RLIMIT_NOFILE as libc::c_int, self.nofile),
+            ("nproc", libc::RLIMIT_NPROC as libc::c_int, self.nproc),
+            (
+                "address_space",
+                libc::RLIMIT_AS as libc::c_int,
+                self.address_space,
+            ),
+            ("cpu", libc::RLIMIT_CPU as libc::c_int, self.cpu),
+            ("fsize", libc::RLIMIT_FSIZE as libc::c_int, self.fsize),
+            ("core", libc::RLIMIT_CORE as libc::c_int, self.core),
+            ("stack", libc::RLIMIT_STACK as libc::c_int, self.stack),
+        ]
+        .into_iter()
+        .filter_map(|(name, res, spec)| spec.map(|s| (name, res, s)))
+        .collect()
+    }
+
+    /// Validate requested limits against the launcher's own limits and
+    /// return them ready for setrlimit.
+    fn rlimits(&self) -> Result<Vec<(libc::c_int, libc::rlimit)>> {
+        let mut out = Vec::new();
+        for (name, res, spec) in self.rlimit_specs() {
+            let (soft, hard) = spec.soft_hard();
+            if soft > hard {
+                return Err(anyhow!(
+                    "limits.{}: soft limit {} exceeds hard limit {}",
+                    name,
+                    soft,
+                    hard
+                ));
+            }
+            let current = getrlimit(res)?;
+            // Raising a hard limit needs CAP_SYS_RESOURCE; fail early with a clear message.
+            if hard > current.rlim_max as u64 && unsafe { libc::geteuid() } != 0 {
+                return Err(anyhow!(
+                    "limits.{}: requested hard limit {} exceeds the launcher's hard limit {}",
+                    name,
+                    hard,
+                    rlim_display(current.rlim_max)
+                ));
+            }
+            out.push((
+                res,
+                libc::rlimit {
+                    rlim_cur: soft as libc::rlim_t,
+                    rlim_max: hard as libc::rlim_t,
+                },
+            ));
+        }
+        Ok(out)
+    }
+
+    /// The escalation sequence; it always ends with KILL so that stopping
+    /// the command cannot hang on a signal the command ignores.
+    fn kill_signals(&self) -> Result<Vec<libc::c_int>> {
+        if self.kill_signals.is_empty() {
+            return Ok(vec![libc::SIGTERM, libc::SIGKILL]);
+        }
+        let mut signals = self
+            .kill_signals
+            .iter()
+            .map(|s| parse_signal(s).map_err(|e| anyhow!("limits.kill_signals: {}", e)))
+            .collect::<Result<Vec<_>>>()?;
+        if signals.last() != Some(&libc::SIGKILL) {
+            signals.push(libc::SIGKILL);
+        }
+        Ok(signals)
+    }
+}
+
+fn getrlimit(res: libc::c_int) -> Result<libc::rlimit> {
+    let mut rl = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit(2) writes into the provided struct.
+    if unsafe { libc::getrlimit(res as _, &mut rl) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(rl)
+}
+
+fn apply_rlimits(rlimits: &[(libc::c_int, libc::rlimit)]) -> std::io::Result<()> {
+    for (res, rl) in rlimits {
+        // SAFETY: setrlimit(2) reads the provided struct.
+        if unsafe { libc::setrlimit(*res as _, rl) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn rlim_display(v: libc::rlim_t) -> String {
+    if v == libc::RLIM_INFINITY {
+        "unlimited".to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+fn print_limits(limits: &Limits) -> Result<()> {
+    let specs = limits.rlimit_specs();
+    if specs.is_empty() && limits.timeout.is_none() && limits.idle_timeout.is_none() {
+        return Ok(());
+    }
+    println!("Limits:");
+    if let Some(t) = limits.timeout {
+        println!("  timeout = {}s", t);
+    }
+    if let Some(t) = limits.idle_timeout {
+        println!("  idle_timeout = {}s", t);
+    }
+    for (name, res, spec) in specs {
+        let (soft, hard) = spec.soft_hard();
+        let current = getrlimit(res)?;
+        println!(
+            "  {}: soft = {}, hard = {} (launcher: soft = {}, hard = {})",
+            name,
+            soft,
+            hard,
+            rlim_display(current.rlim_cur),
+            rlim_display(current.rlim_max)
+        );
+    }
+    Ok(())
+}
+
+/// Parse `TERM`, `SIGTERM` or a signal number (1 to SIGRTMAX).
+fn parse_signal(name: &str) -> Result<libc::c_int> {
+    if let Ok(n) = name.parse::<libc::c_int>() {
+        let max = libc::SIGRTMAX();
+        if !(1..=max).contains(&n) {
+            return Err(anyhow!("signal {} out of range 1..={}", n, max));
+        }
+        return Ok(n);
+    }
+    let upper = name.to_ascii_uppercase();
+    let sig = match upper.strip_prefix("SIG").unwrap_or(&upper) {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "PIPE" => libc::SIGPIPE,
+        "ALRM" => libc::SIGALRM,
+        "TERM" => libc::SIGTERM,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        _ => return Err(anyhow!("unknown signal: {}", name)),
+    };
+    Ok(sig)
+}
+
+/// Which launcher environment variables the command starts with, before
+/// `env_deny`, the built-in secret denylist and `command.env` are applied.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum EnvPolicy {
+    /// Everything from the launcher.
+    #[default]
+    Inherit,
+    /// Nothing; only `command.env`.
+    Clear,
+    /// Only variables matching `env_passthrough`.
+    Allowlist,
+}
+
+/// `exec` replaces the launcher with the command (same PID, no wrapper
+/// process); `supervise` spawns the command and waits for it.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+enum ExecMode {
+    Exec,
+    #[default]
+    Supervise,
+}
+
+/// `always` runs the command on a new pseudo-terminal, `auto` only when the
+/// launcher's stdin is not a terminal, `never` (default) leaves stdio as is.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+enum TtyMode {
+    Auto,
+    Always,
+    #[default]
+    Never,
+}
+
+impl TtyMode {
+    fn name(self) -> &'static str {
+        match self {
+            TtyMode::Auto => "auto",
+            TtyMode::Always => "always",
+            TtyMode::Never => "never",
+        }
+    }
+
+    fn wants_pty(self) -> bool {
+        match self {
+            // SAFETY: isatty only queries the descriptor.
+            TtyMode::Auto => unsafe { libc::isatty(libc::STDIN_FILENO) != 1 },
+            TtyMode::Always => true,
+            TtyMode::Never => false,
+        }
+    }
 }
 
 fn load_config(path: &PathBuf) -> Result<Config> {
@@ -825,6 +7114,241 @@ fn normalize_path(p: &str) -> Result<String> {
     }
 }
 
+// --------------- Protected paths ---------------
+
+impl Protect {
+    fn name(self) -> &'static str {
+        match self {
+            Protect::Vcs => "vcs",
+            Protect::Dotenv => "dotenv",
+            Protect::CiConfig => "ci_config",
+        }
+    }
+
+    /// Upper bound of rights kept on a protected path.
+    fn allowed(self) -> BitFlags<AccessFs> {
+        match self {
+            Protect::Vcs | Protect::CiConfig => {
+                AccessFs::ReadFile | AccessFs::ReadDir | AccessFs::Execute
+            }
+            Protect::Dotenv => BitFlags::empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ProtectedPath {
+    kind: Protect,
+    path: PathBuf,
+    allowed: BitFlags<AccessFs>,
+    is_dir: bool,
+}
+
+/// Directory levels below each access root searched for protected entries:
+/// 0 is the root itself, 2 covers `projects: [~/src]` holding
+/// `~/src/<org>/<repo>/.git`.
+const PROTECT_SEARCH_DEPTH: usize = 2;
+
+/// Directories searched for protected entries: the root and its
+/// subdirectories down to PROTECT_SEARCH_DEPTH, without following symlinks
+/// or descending into `.git`.
+fn protect_search_dirs(root: &std::path::Path) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    let mut level = vec![root.to_path_buf()];
+    for _ in 0..PROTECT_SEARCH_DEPTH {
+        let mut next = Vec::new();
+        for dir in &level {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|t| t.is_dir()) && entry.file_name() != ".git" {
+                    next.push(entry.path());
+                }
+            }
+        }
+        next.sort();
+        dirs.extend(next.iter().cloned());
+        level = next;
+    }
+    dirs
+}
+
+/// Whether `path` or one of its ancestors below `root` is a symlink (or
+/// missing). A rule on such a path would apply to the symlink's target.
+fn symlink_below(root: &std::path::Path, path: &std::path::Path) -> bool {
+    path.ancestors()
+        .take_while(|p| *p != root)
+        .any(|p| fs::symlink_metadata(p).map_or(true, |m| m.file_type().is_symlink()))
+}
+
+/// Find protected entries (`.git`, `.env*`, `.github/workflows`) that
+/// currently exist in the profile's access roots or a few levels below.
+/// Symlinked entries are skipped; `gitdir:` targets are resolved.
+fn plan_protections(profile: &Profile) -> Result<Vec<ProtectedPath>> {
+    let mut out: Vec<ProtectedPath> = Vec::new();
+    if profile.protect.is_empty() {
+        return Ok(out);
+    }
+    let mut push = |kind: Protect, path: PathBuf| {
+        if path.exists() && !out.iter().any(|t| t.path == path) {
+            out.push(ProtectedPath {
+                kind,
+                is_dir: path.is_dir(),
+                allowed: kind.allowed(),
+                path,
+            });
+        }
+    };
+    for group in profile.access_roots.values() {
+        for p in &group.paths {
+            let top = PathBuf::from(normalize_path(p)?);
+            for root in protect_search_dirs(&top) {
+                for kind in &profile.protect {
+                    match kind {
+                        Protect::Vcs => {
+                            let dot_git = root.join(".git");
+                            if symlink_below(&root, &dot_git) {
+                                continue;
+                            }
+                            if dot_git.is_file() {
+                                // Worktrees and submodules: `.git` is a file pointing elsewhere.
+                                if let Some(gitdir) = read_gitdir_file(&dot_git)
+                                    .and_then(|g| fs::canonicalize(root.join(g)).ok())
+                                {
+                                    if let Some(common) = read_commondir(&gitdir)
+                                        .and_then(|c| fs::canonicalize(c).ok())
+                                    {
+                                        push(Protect::Vcs, common);
+                                    }
+                                    push(Protect::Vcs, gitdir);
+                                }
+                            }
+                            push(Protect::Vcs, dot_git);
+                        }
+                        Protect::Dotenv => {
+                            if let Ok(entries) = fs::read_dir(&root) {
+                                for entry in entries.flatten() {
+                                    if entry.file_name().to_string_lossy().starts_with(".env")
+                                        && !entry.file_type().is_ok_and(|t| t.is_symlink())
+                                    {
+                                        push(Protect::Dotenv, entry.path());
+                                    }
+                                }
+                            }
+                        }
+                        Protect::CiConfig => {
+                            let workflows = root.join(".github/workflows");
+                            if !symlink_below(&root, &workflows) {
+                                push(Protect::CiConfig, workflows);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // A `gitdir:` target outside every group gets no rule at all.
+    let roots = profile
+        .access_roots
+        .values()
+        .flat_map(|g| g.paths.iter())
+        .map(|p| normalize_path(p).map(PathBuf::from))
+        .collect::<Result<Vec<_>>>()?;
+    out.retain(|t| roots.iter().any(|r| t.path.starts_with(r)));
+    Ok(out)
+}
+
+fn read_gitdir_file(dot_git: &std::path::Path) -> Option<PathBuf> {
+    let text = fs::read_to_string(dot_git).ok()?;
+    text.lines()
+        .find_map(|l| l.strip_prefix("gitdir:"))
+        .map(|g| PathBuf::from(g.trim()))
+}
+
+fn read_commondir(gitdir: &std::path::Path) -> Option<PathBuf> {
+    let text = fs::read_to_string(gitdir.join("commondir")).ok()?;
+    let common = text.trim();
+    if common.is_empty() {
+        return None;
+    }
+    Some(gitdir.join(common))
+}
+
+/// Expand a group into (path, access) rules. Landlock grants the union of
+/// rights over all ancestors, so a protected path cannot be carved out of a
+/// broader rule: instead, directories leading to a protected path only get
+/// rights the protected path may keep, and their other children get the
+/// group's full rights. Entries created later directly in such directories
+/// only receive the reduced rights.
+fn group_rules(
+    paths: &[String],
+    allowed: BitFlags<AccessFs>,
+    protected: &[ProtectedPath],
+) -> Vec<(PathBuf, BitFlags<AccessFs>)> {
+    let mut rules = Vec::new();
+    for p in paths {
+        split_rules(&PathBuf::from(p), allowed, protected, &mut rules);
+    }
+    rules
+}
+
+fn split_rules(
+    dir: &std::path::Path,
+    allowed: BitFlags<AccessFs>,
+    protected: &[ProtectedPath],
+    rules: &mut Vec<(PathBuf, BitFlags<AccessFs>)>,
+) {
+    // The rule itself sits inside (or on) a protected path.
+    if let Some(t) = protected.iter().find(|t| dir.starts_with(&t.path)) {
+        let access = allowed & t.allowed;
+        if !access.is_empty() {
+            rules.push((dir.to_path_buf(), access));
+        }
+        return;
+    }
+    // Only protections that actually take rights away from this group matter.
+    let beneath: Vec<&ProtectedPath> = protected
+        .iter()
+        .filter(|t| t.path.starts_with(dir) && !(allowed & !t.allowed).is_empty())
+        .collect();
+    if beneath.is_empty() {
+        rules.push((dir.to_path_buf(), allowed));
+        return;
+    }
+
+    let mut dir_access = allowed;
+    for t in &beneath {
+        let mut floor = t.allowed;
+        if !t.is_dir {
+            // Directory-only rights on an ancestor do not reach a file's content.
+            floor.insert(AccessFs::ReadDir);
+        }
+        dir_access &= floor;
+    }
+    if !dir_access.is_empty() {
+        rules.push((dir.to_path_buf(), dir_access));
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        // A rule on a symlink would grant the group's rights on its target,
+        // wherever that is; the link itself is covered by `dir`'s rule.
+        if entry.file_type().map_or(true, |t| t.is_symlink()) {
+            continue;
+        }
+        let child = entry.path();
+        if beneath.iter().any(|t| t.path.starts_with(&child)) {
+            split_rules(&child, allowed, protected, rules);
+        } else {
+            rules.push((child, allowed));
+        }
+    }
+}
+
 // --------------- Access mapping helpers ---------------
 
 fn access_from_permissions(perms: &Permissions) -> BitFlags<AccessFs> {
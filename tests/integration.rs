@@ -1,4 +1,5 @@
 use assert_cmd::Command;
+use predicates::prelude::*;
 use predicates::str::contains;
 use serde_yaml::Value;
 
@@ -141,3 +142,1289 @@ fn generate_profile_output_structure() {
         "projects.paths should contain the provided root"
     );
 }
+
+#[test]
+fn exec_mode_exec_propagates_exit_code() {
+    let mut cmd = bin_cmd();
+    cmd.arg("--root")
+        .arg("/tmp")
+        .arg("--exec-mode")
+        .arg("exec")
+        .arg("--")
+        .arg("/bin/sh")
+        .arg("-c")
+        .arg("exit 7");
+    cmd.assert().code(7);
+}
+
+/// Run a profile with a pre hook and `exec_mode: exec` (unless `args`
+/// override it); the launcher must refuse it with `error` before the hook
+/// (or anything else) runs.
+fn assert_refused_before_hooks(
+    name: &str,
+    profile_extra: &str,
+    command_extra: &str,
+    args: &[&str],
+    error: &str,
+) {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "ai-sandbox-integ-refuse-{}-{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let marker = dir.join("hook-ran");
+    let cfg = dir.join("cfg.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            "version: 1\nprofiles:\n  p:\n{}    hooks:\n      pre: [\"touch {}\"]\n    command:\n      binary: /bin/true\n      exec_mode: exec\n{}",
+            profile_extra,
+            marker.display(),
+            command_extra
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .args(args);
+    cmd.assert().failure().stderr(contains(error));
+    assert!(!marker.exists(), "{}: pre hook ran", name);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn exec_mode_conflicts_are_refused_before_side_effects() {
+    assert_refused_before_hooks(
+        "timeout",
+        "",
+        "      limits: { timeout: 5 }\n",
+        &[],
+        "timeout and idle_timeout require exec_mode supervise",
+    );
+    assert_refused_before_hooks(
+        "tee",
+        "",
+        "      stdio: { tee: /dev/null }\n",
+        &[],
+        "stdio.tee requires exec_mode supervise",
+    );
+    assert_refused_before_hooks(
+        "tty",
+        "",
+        "",
+        &["--tty", "always"],
+        "tty requires exec_mode supervise",
+    );
+    assert_refused_before_hooks(
+        "tty-stdio",
+        "",
+        "      stdio: { stdout: { file: /dev/null } }\n",
+        &["--tty", "always", "--exec-mode", "supervise"],
+        "tty cannot be combined with stdio",
+    );
+    let report = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "ai-sandbox-integ-refuse-report-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&report, "previous").unwrap();
+    assert_refused_before_hooks(
+        "report",
+        "",
+        "",
+        &["--report", report.to_str().unwrap()],
+        "--report requires exec_mode supervise",
+    );
+    assert_refused_before_hooks(
+        "pid",
+        "    namespaces: { user: true, mount: true, pid: true }\n",
+        "",
+        &[],
+        "namespaces.pid requires exec_mode supervise",
+    );
+    assert_refused_before_hooks(
+        "tmpdir",
+        "    tmpdir: private\n",
+        "",
+        &[],
+        "tmpdir: private requires exec_mode supervise",
+    );
+    assert_refused_before_hooks(
+        "hide",
+        "    namespaces: { hide: [/nonexistent] }\n",
+        "",
+        &["--exec-mode", "supervise"],
+        "namespaces.hide requires namespaces.mount",
+    );
+    assert_refused_before_hooks(
+        "mount-source",
+        "    namespaces: { user: true, mount: true }\n    mounts: [{ source: /nonexistent-source, target: /tmp }]\n",
+        "",
+        &["--exec-mode", "supervise"],
+        "mount source /nonexistent-source",
+    );
+    assert_eq!(std::fs::read_to_string(&report).unwrap(), "previous");
+    std::fs::remove_file(&report).unwrap();
+}
+
+#[test]
+fn strict_rejects_kept_directory_fd_outside_allowed_paths() {
+    let cfg = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "ai-sandbox-integ-strict-fd-{}.yaml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    access_roots:\n      system:\n        paths: [/usr]\n        permissions: { read_file: true, read_dir: true, execute: true }\n    keep_fds: [3]\n    command: { binary: /bin/true }\n",
+    )
+    .unwrap();
+    // fd 3 is a directory outside every allowed path.
+    let launch = |extra: &[&str]| {
+        let mut cmd = std::process::Command::new("/bin/sh");
+        cmd.arg("-c")
+            .arg("exec 3</etc; exec \"$0\" \"$@\"")
+            .arg(assert_cmd::cargo::cargo_bin!("ai-sandbox-landlock"))
+            .arg("--config")
+            .arg(&cfg)
+            .arg("--profile")
+            .arg("p")
+            .args(extra);
+        assert_cmd::Command::from_std(cmd).assert()
+    };
+
+    launch(&[])
+        .success()
+        .stderr(contains("inherited fd 3 refers to directory /etc"));
+    launch(&["--strict"])
+        .failure()
+        .stderr(contains("kept fd 3 refers to directory /etc"));
+
+    std::fs::remove_file(&cfg).unwrap();
+}
+
+#[test]
+fn supervise_reports_signal_as_128_plus_signo() {
+    let mut cmd = bin_cmd();
+    cmd.arg("--root")
+        .arg("/tmp")
+        .arg("--")
+        .arg("/bin/sh")
+        .arg("-c")
+        .arg("kill -TERM $$");
+    cmd.assert().code(128 + 15);
+}
+
+#[test]
+fn supervise_forwards_sigterm_to_child() {
+    let mut child =
+        std::process::Command::new(assert_cmd::cargo::cargo_bin!("ai-sandbox-landlock"))
+            .arg("--root")
+            .arg("/tmp")
+            .arg("--")
+            .arg("/bin/sh")
+            .arg("-c")
+            .arg("echo ready; exec sleep 30")
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .expect("spawn launcher");
+    // Signal only once the command runs, not while the launcher starts up.
+    let mut ready = String::new();
+    std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(child.stdout.take().unwrap()),
+        &mut ready,
+    )
+    .unwrap();
+    assert_eq!(ready, "ready\n");
+    let killed = std::process::Command::new("kill")
+        .arg("-TERM")
+        .arg(child.id().to_string())
+        .status()
+        .expect("run kill");
+    assert!(killed.success());
+    let status = child.wait().expect("wait launcher");
+    assert_eq!(status.code(), Some(128 + 15));
+}
+
+#[test]
+fn dry_run_prints_env_keys_without_values() {
+    let mut cmd = bin_cmd();
+    cmd.env("GITHUB_TOKEN", "ghp_secret_value")
+        .env("AI_SANDBOX_VISIBLE", "visible_value")
+        .arg("--root")
+        .arg("/tmp")
+        .arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(contains("Environment (env_policy=inherit)"))
+        .stdout(contains("\"AI_SANDBOX_VISIBLE\""))
+        .stdout(contains("removed = "))
+        .stdout(contains("GITHUB_TOKEN"))
+        .stdout(contains("visible_value").not())
+        .stdout(contains("ghp_secret_value").not());
+}
+
+#[test]
+fn print_config_redacts_env_secrets() {
+    let cfg = std::env::temp_dir().join("ai-sandbox-integ-redact.yaml");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/true\n      env:\n        GITHUB_TOKEN: plain-value\n        LANG: C.UTF-8\n",
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--print-config");
+    cmd.assert()
+        .success()
+        .stdout(contains("GITHUB_TOKEN: <redacted>"))
+        .stdout(contains("LANG: C.UTF-8"))
+        .stdout(contains("plain-value").not());
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--print-config")
+        .arg("--show-secrets");
+    cmd.assert().success().stdout(contains("plain-value"));
+}
+
+#[test]
+fn timeout_stops_command_with_exit_124() {
+    let mut cmd = bin_cmd();
+    cmd.arg("--root")
+        .arg("/tmp")
+        .arg("--timeout")
+        .arg("1")
+        .arg("--")
+        .arg("/bin/sleep")
+        .arg("30");
+    cmd.timeout(std::time::Duration::from_secs(20));
+    cmd.assert().code(124);
+}
+
+#[test]
+fn kill_signals_end_with_kill_and_reject_bad_numbers() {
+    let cfg = std::env::temp_dir().join(format!(
+        "ai-sandbox-integ-kill-signals-{}.yaml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"trap '' TERM; sleep 30\"]\n      limits:\n        timeout: 1\n        kill_signals: [TERM]\n        kill_grace: 1\n",
+    )
+    .unwrap();
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    cmd.timeout(std::time::Duration::from_secs(20));
+    cmd.assert().code(124);
+
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/true\n      limits:\n        kill_signals: [\"0\"]\n",
+    )
+    .unwrap();
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--dry-run");
+    cmd.assert()
+        .failure()
+        .stderr(contains("limits.kill_signals: signal 0 out of range"));
+
+    std::fs::remove_file(&cfg).unwrap();
+}
+
+#[test]
+fn idle_timeout_relays_output_and_stops_command() {
+    let mut cmd = bin_cmd();
+    cmd.arg("--root")
+        .arg("/tmp")
+        .arg("--idle-timeout")
+        .arg("1")
+        .arg("--")
+        .arg("/bin/sh")
+        .arg("-c")
+        .arg("echo started; sleep 30");
+    cmd.timeout(std::time::Duration::from_secs(20));
+    cmd.assert().code(124).stdout(contains("started"));
+}
+
+#[test]
+fn rlimits_are_applied_and_shown_in_dry_run() {
+    let cfg = std::env::temp_dir().join("ai-sandbox-integ-limits.yaml");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"ulimit -n\"]\n      limits:\n        nofile: 64\n",
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(contains("Limits:"))
+        .stdout(contains("nofile: soft = 64, hard = 64"));
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    cmd.assert().success().stdout(contains("64"));
+}
+
+#[test]
+fn hardening_new_session_and_dry_run() {
+    let cfg = std::env::temp_dir().join("ai-sandbox-integ-hardening.yaml");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"read -r _ _ _ _ _ sid _ < /proc/$$/stat; [ \\\"$sid\\\" = \\\"$$\\\" ]\"]\n    hardening:\n      dumpable: false\n      pdeathsig: SIGKILL\n      new_session: true\n",
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(contains("Hardening:"))
+        .stdout(contains("pdeathsig = SIGKILL (9)"))
+        .stdout(contains("new_session = true"))
+        .stdout(contains(
+            "dumpable = false (core dumps disabled; execve resets the flag",
+        ));
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    cmd.assert().success();
+}
+
+#[test]
+fn inherited_fds_are_closed_before_running() {
+    let bin = assert_cmd::cargo::cargo_bin!("ai-sandbox-landlock");
+    let script = format!(
+        "exec 7</ ; exec {} --root /usr -- /bin/sh -c 'test ! -e /proc/self/fd/7'",
+        bin.display()
+    );
+    let mut cmd = Command::new("/bin/sh");
+    cmd.arg("-c").arg(script);
+    cmd.assert().success().stderr(contains(
+        "inherited fd 7 refers to directory / outside all allowed paths",
+    ));
+}
+
+#[test]
+fn dry_run_preflight_reports_denied_binary() {
+    let mut cmd = bin_cmd();
+    cmd.arg("--root")
+        .arg("/tmp")
+        .arg("--read-only")
+        .arg("--dry-run")
+        .arg("--")
+        .arg("sh");
+    cmd.assert()
+        .success()
+        .stdout(contains("Preflight:"))
+        .stdout(contains("DENIED (missing [\"Execute\"]) [command]"));
+}
+
+#[test]
+fn stdio_redirects_stdout_and_tees_output() {
+    let dir = std::env::temp_dir().join(format!("ai-sandbox-integ-stdio-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let out = dir.join("out.log");
+    let tee = dir.join("tee.log");
+    let cfg = dir.join("stdio.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"echo to-out; echo to-err >&2\"]\n      stdio:\n        stdin: null\n        stdout: {{file: {}}}\n        tee: {}\n",
+            out.display(),
+            tee.display()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(contains("Stdio:"))
+        .stdout(contains("stdin = null"));
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    cmd.assert()
+        .success()
+        .stdout(contains("to-out").not())
+        .stderr(contains("to-err"));
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "to-out\n");
+    let teed = std::fs::read_to_string(&tee).unwrap();
+    assert!(teed.contains("to-out") && teed.contains("to-err"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tty_always_runs_command_on_a_pty() {
+    let mut cmd = bin_cmd();
+    cmd.arg("--root")
+        .arg("/tmp")
+        .arg("--tty")
+        .arg("always")
+        .arg("--")
+        .arg("/bin/sh")
+        .arg("-c")
+        .arg("test -t 0 && test -t 1 && test -t 2 && stty size");
+    cmd.write_stdin("");
+    cmd.assert().success().stdout(contains("24 80"));
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--root")
+        .arg("/tmp")
+        .arg("--tty")
+        .arg("always")
+        .arg("--dry-run")
+        .arg("--")
+        .arg("/bin/true");
+    cmd.assert()
+        .success()
+        .stdout(contains("tty = always (pty allocated)"));
+}
+
+#[test]
+fn hooks_run_around_command_with_exit_code() {
+    let dir = std::env::temp_dir().join(format!("ai-sandbox-integ-hooks-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("hooks.log");
+    let cfg = dir.join("hooks.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"echo cmd >> {log}; exit 3\"]\n    hooks:\n      pre: [\"echo pre >> {log}\"]\n      post: [\"echo post=$EXIT_CODE >> {log}\"]\n",
+            log = log.display()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(contains("Hooks:"))
+        .stdout(contains("post: echo post=$EXIT_CODE"));
+    assert!(!log.exists());
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    cmd.assert().code(3);
+    assert_eq!(std::fs::read_to_string(&log).unwrap(), "pre\ncmd\npost=3\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn hook_commands_are_redacted_unless_show_secrets() {
+    let cfg = std::env::temp_dir().join(format!(
+        "ai-sandbox-integ-hook-redact-{}.yaml",
+        std::process::id()
+    ));
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/true\n    hooks:\n      pre: [\"true ghp_hooksecret0123456789\"]\n      post: [\"true ghp_hooksecret0123456789\"]\n",
+    )
+    .unwrap();
+
+    for flag in ["--print-config", "--dry-run"] {
+        let mut cmd = bin_cmd();
+        cmd.arg("--config")
+            .arg(&cfg)
+            .arg("--profile")
+            .arg("p")
+            .arg(flag);
+        cmd.assert()
+            .success()
+            .stdout(contains("true <redacted>"))
+            .stdout(contains("ghp_hooksecret").not());
+
+        let mut cmd = bin_cmd();
+        cmd.arg("--config")
+            .arg(&cfg)
+            .arg("--profile")
+            .arg("p")
+            .arg(flag)
+            .arg("--show-secrets");
+        cmd.assert()
+            .success()
+            .stdout(contains("true ghp_hooksecret0123456789"));
+    }
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--log-level")
+        .arg("info");
+    cmd.assert()
+        .success()
+        .stderr(contains("running pre hook: true <redacted>"))
+        .stderr(contains("ghp_hooksecret").not());
+
+    std::fs::remove_file(&cfg).unwrap();
+}
+
+#[test]
+fn private_tmpdir_is_exported_and_removed() {
+    let cfg = std::env::temp_dir().join("ai-sandbox-integ-tmpdir.yaml");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    tmpdir: private\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"[ \\\"$TMP\\\" = \\\"$TMPDIR\\\" ] && touch \\\"$TMPDIR/x\\\" && stat -c %a \\\"$TMPDIR\\\" && echo \\\"dir=$TMPDIR\\\"\"]\n",
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    let out = cmd
+        .assert()
+        .success()
+        .stdout(contains("700"))
+        .get_output()
+        .stdout
+        .clone();
+    let out = String::from_utf8(out).unwrap();
+    let dir = out.lines().find_map(|l| l.strip_prefix("dir=")).unwrap();
+    assert!(dir.contains("ai-sandbox."));
+    assert!(!std::path::Path::new(dir).exists());
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--exec-mode")
+        .arg("exec");
+    cmd.assert()
+        .failure()
+        .stderr(contains("tmpdir: private requires exec_mode supervise"));
+}
+
+#[test]
+fn report_is_written_as_json() {
+    let dir = std::env::temp_dir().join(format!("ai-sandbox-integ-report-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cfg = dir.join("report.yaml");
+    let report = dir.join("report.json");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"exit 5\"]\n",
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--report")
+        .arg(&report);
+    cmd.assert().code(5);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(json["profile"], "p");
+    assert_eq!(json["exit_code"], 5);
+    assert!(json["signal"].is_null());
+    assert_eq!(json["config_sha256"].as_str().unwrap().len(), 64);
+    assert!(json["rusage"]["max_rss_kb"].as_i64().unwrap() > 0);
+    assert!(json["started_at"].as_str().unwrap().ends_with('Z'));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn mount_namespace_gives_private_tmp_and_hides_paths() {
+    // Outside /tmp, which the namespace replaces anyway.
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("ai-sandbox-integ-ns-{}", std::process::id()));
+    let secret = dir.join("secret");
+    std::fs::create_dir_all(&secret).unwrap();
+    std::fs::write(secret.join("key"), "k").unwrap();
+    let cfg = dir.join("ns.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            "version: 1\nprofiles:\n  p:\n    namespaces:\n      user: true\n      mount: true\n      hide: [{}]\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"ls -A /tmp && ls -A {} && touch /tmp/inside\"]\n",
+            secret.display(),
+            secret.display()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    let out = cmd.output().unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if stderr.contains("cannot create namespaces") {
+        eprintln!("skipping: user namespaces unavailable");
+    } else {
+        assert!(out.status.success(), "{}", stderr);
+        assert!(out.stdout.is_empty());
+        assert!(secret.join("key").exists());
+        assert!(!std::path::Path::new("/tmp/inside").exists());
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn network_none_leaves_only_loopback() {
+    let cfg = std::env::temp_dir().join("ai-sandbox-integ-network.yaml");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    network: none\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"tail -n +3 /proc/net/dev | cut -d: -f1 | tr -d ' '\"]\n",
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    let out = cmd.output().unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if stderr.contains("cannot create namespaces") {
+        eprintln!("skipping: user namespaces unavailable");
+        return;
+    }
+    assert!(out.status.success(), "{}", stderr);
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "lo\n");
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--check");
+    cmd.assert()
+        .stdout(contains("Unprivileged user namespaces permitted:"));
+}
+
+#[test]
+fn pid_namespace_runs_command_under_reaping_init() {
+    let cfg = std::env::temp_dir().join("ai-sandbox-integ-pid.yaml");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  p:\n    namespaces:\n      user: true\n      mount: true\n      pid: true\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"echo $$; (sleep 0.1 &); sleep 0.3; cat /proc/[0-9]*/stat | cut -d' ' -f3 | tr -d '\\\\n'; echo; exit 7\"]\n",
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    let out = cmd.output().unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if stderr.contains("cannot create namespaces") {
+        eprintln!("skipping: user namespaces unavailable");
+        return;
+    }
+    assert_eq!(out.status.code(), Some(7), "{}", stderr);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("2"));
+    // The orphaned sleep was reparented to init and reaped.
+    let states = lines.next().unwrap();
+    assert!(!states.contains('Z'), "{}", states);
+}
+
+#[test]
+fn seccomp_deny_with_kill_action_terminates_command() {
+    let cfg = std::env::temp_dir().join("ai-sandbox-integ-seccomp.yaml");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  errno:\n    seccomp: {preset: none, deny: [getppid]}\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"echo $PPID\"]\n  kill:\n    seccomp: {preset: strict, action: kill, deny: [getppid]}\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"echo $PPID\"]\n",
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("errno");
+    cmd.assert().success().stdout("-1\n");
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("kill");
+    cmd.assert().code(128 + 31);
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("kill")
+        .arg("--dry-run");
+    cmd.assert()
+        .success()
+        .stdout(contains("preset = strict"))
+        .stdout(contains("unshare"));
+}
+
+#[test]
+fn bind_mounts_remap_paths_inside_mount_namespace() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("ai-sandbox-integ-binds-{}", std::process::id()));
+    let (models, target) = (dir.join("models"), dir.join("target"));
+    std::fs::create_dir_all(&models).unwrap();
+    std::fs::create_dir_all(&target).unwrap();
+    std::fs::write(models.join("weights"), "w").unwrap();
+    let cfg = dir.join("binds.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            "version: 1\nprofiles:\n  p:\n    namespaces: {{user: true, mount: true}}\n    mounts:\n      - {{source: {}, target: {}, readonly: true}}\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"cat {t}/weights && ! touch {t}/new 2>/dev/null\"]\n",
+            models.display(),
+            target.display(),
+            t = target.display()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    let out = cmd.output().unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if stderr.contains("cannot create namespaces") {
+        eprintln!("skipping: user namespaces unavailable");
+    } else {
+        assert!(out.status.success(), "{}", stderr);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "w");
+        assert!(!models.join("new").exists());
+        assert!(!target.join("weights").exists());
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn capabilities_are_dropped_and_root_needs_allow_root() {
+    let cfg = std::env::temp_dir().join("ai-sandbox-integ-caps.yaml");
+    std::fs::write(
+        &cfg,
+        "version: 1\nprofiles:\n  refuse:\n    capabilities: {}\n    command: {binary: /bin/true}\n  untouched:\n    command:\n      binary: /bin/grep\n      args: [\"^CapEff\", /proc/self/status]\n  keep:\n    capabilities: {keep: [net_bind_service], allow_root: true}\n    command:\n      binary: /bin/sh\n      args: [\"-c\", \"grep -E '^Cap(Eff|Bnd|Amb)' /proc/self/status\"]\n",
+    )
+    .unwrap();
+    // SAFETY: geteuid cannot fail.
+    let root = unsafe { libc::geteuid() } == 0;
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("refuse");
+    if root {
+        cmd.assert()
+            .failure()
+            .stderr(contains("refusing to run as root"));
+    } else {
+        cmd.assert().success();
+    }
+
+    // Without the section, root is not refused and keeps its capabilities.
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("untouched");
+    if root {
+        cmd.assert()
+            .success()
+            .stdout(contains("CapEff:\t0000000000000000").not())
+            .stderr(contains("running as root without a capabilities section"));
+    } else {
+        cmd.assert().success();
+    }
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("keep");
+    let out = cmd.output().unwrap();
+    assert!(out.status.success());
+    let status = String::from_utf8_lossy(&out.stdout);
+    if root {
+        assert!(status.contains("CapEff:\t0000000000000400"), "{}", status);
+        assert!(status.contains("CapBnd:\t0000000000000400"), "{}", status);
+    } else {
+        assert!(status.contains("CapEff:\t0000000000000000"), "{}", status);
+        assert!(status.contains("CapAmb:\t0000000000000000"), "{}", status);
+    }
+}
+
+#[test]
+fn print_ruleset_warns_about_mounts_nested_under_allowed_paths() {
+    let mut cmd = bin_cmd();
+    cmd.arg("--root").arg("/").arg("--print-ruleset");
+    cmd.assert()
+        .success()
+        .stdout(contains("Nested mounts:"))
+        .stdout(contains(
+            "warning: /proc: proc from proc, nested under allowed path /",
+        ));
+}
+
+#[test]
+fn learn_records_accesses_and_emits_profile() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("ai-sandbox-integ-learn-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("old"), "x").unwrap();
+    let (record, output) = (dir.join("record.tsv"), dir.join("learned.yaml"));
+
+    let mut cmd = bin_cmd();
+    cmd.arg("learn")
+        .arg("--depth")
+        .arg("64")
+        .arg("--name")
+        .arg("t")
+        .arg("--record")
+        .arg(&record)
+        .arg("--output")
+        .arg(&output)
+        .arg("--")
+        .arg("/bin/sh")
+        .arg("-c")
+        .arg("cat /etc/passwd >/dev/null && echo x > new && rm old")
+        .current_dir(&dir);
+    let out = cmd.output().unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if stderr.contains("Operation not permitted") {
+        eprintln!("skipping: ptrace not permitted");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    assert!(out.status.success(), "{}", stderr);
+
+    let record = std::fs::read_to_string(&record).unwrap();
+    assert!(record.contains("read\t/etc/passwd\n"), "{}", record);
+    let dir_real = std::fs::canonicalize(&dir).unwrap();
+    let new = format!("create\t{}/new\n", dir_real.display());
+    let old = format!("delete\t{}/old\n", dir_real.display());
+    assert!(record.contains(&new) && record.contains(&old), "{}", record);
+
+    let yaml: Value = serde_yaml::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+    let roots = &yaml["profiles"]["t"]["access_roots"];
+    let system: Vec<&str> = roots["system"]["paths"]
+        .as_sequence()
+        .unwrap()
+        .iter()
+        .filter_map(|p| p.as_str())
+        .collect();
+    assert!(system.contains(&"/etc"), "{:?}", system);
+    let group = roots
+        .as_mapping()
+        .unwrap()
+        .values()
+        .find(|g| g["paths"][0].as_str() == Some(dir_real.to_str().unwrap()))
+        .expect("group for the working directory");
+    assert_eq!(group["permissions"]["write_file"].as_bool(), Some(true));
+    assert_eq!(group["permissions"]["remove_file"].as_bool(), Some(true));
+    assert_eq!(
+        yaml["profiles"]["t"]["command"]["binary"].as_str(),
+        Some("/bin/sh")
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn suggest_from_strace_prints_patch_for_uncovered_operations() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("ai-sandbox-integ-suggest-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let cfg = dir.join("cfg.yaml");
+    std::fs::write(
+        &cfg,
+        r#"version: 1
+profiles:
+  dev:
+    access_roots:
+      system:
+        paths: [/usr, /etc]
+        permissions: { read_file: true, read_dir: true, execute: true }
+      projects:
+        paths: [/srv/proj]
+        permissions: { read_file: true, read_dir: true }
+    control_access: { read_file: true, read_dir: true, execute: true, write_file: true }
+    command: { binary: /usr/bin/make, working_dir: /srv/proj }
+"#,
+    )
+    .unwrap();
+    let log = dir.join("strace.log");
+    std::fs::write(
+        &log,
+        r#"10 execve("/usr/bin/make", ["make"], 0x7ffc /* 3 vars */) = 0
+10 openat(AT_FDCWD, "Makefile", O_RDONLY) = 3
+10 openat(AT_FDCWD, "build/out.o", O_WRONLY|O_CREAT|O_TRUNC, 0666) = -1 EACCES (Permission denied)
+10 openat(AT_FDCWD, "/srv/proj/README", O_RDONLY) = -1 EACCES (Permission denied)
+10 unlink("/srv/proj/build/old.o") = 0
+"#,
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("suggest")
+        .arg("--from-strace")
+        .arg(&log)
+        .arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("dev");
+    cmd.assert()
+        .success()
+        .stdout(contains("#   write /srv/proj/build/out.o (denied)"))
+        .stdout(contains(
+            "# denied although the profile allows them (DAC, an LSM or another sandbox):\n#   read /srv/proj/README (denied)",
+        ))
+        .stdout(contains("Makefile").not())
+        // remove_file is not handled by the profile, so unlink needs nothing.
+        .stdout(contains("old.o").not())
+        .stdout(contains(
+            "profiles:\n  dev:\n    access_roots:\n      build:\n        paths:\n        - /srv/proj/build\n",
+        ))
+        .stdout(contains("write_file: true"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn explain_reports_granting_groups_and_denied_rights() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("ai-sandbox-integ-explain-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("proj/src")).unwrap();
+    std::fs::create_dir_all(dir.join("outside")).unwrap();
+    let dir = std::fs::canonicalize(&dir).unwrap();
+    let (proj, outside) = (dir.join("proj"), dir.join("outside"));
+    std::fs::write(proj.join("src/main.rs"), "").unwrap();
+    std::fs::write(outside.join("secret"), "").unwrap();
+    std::os::unix::fs::symlink(outside.join("secret"), proj.join("escape")).unwrap();
+    let cfg = dir.join("cfg.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            r#"version: 1
+profiles:
+  dev:
+    access_roots:
+      projects:
+        paths: [{}]
+        permissions: {{ read_file: true, read_dir: true, write_file: true }}
+    control_access: {{ read_file: true, read_dir: true, write_file: true, remove_file: true }}
+    command: {{ binary: /bin/true }}
+"#,
+            proj.display()
+        ),
+    )
+    .unwrap();
+    let explain = |path: &std::path::Path, access: &str| {
+        let mut cmd = bin_cmd();
+        cmd.arg("explain")
+            .arg(path)
+            .arg("--access")
+            .arg(access)
+            .arg("--config")
+            .arg(&cfg)
+            .arg("--profile")
+            .arg("dev");
+        cmd.assert().success()
+    };
+
+    explain(
+        &proj.join("src/main.rs"),
+        "write_file,remove_file,remove_dir",
+    )
+    .stdout(contains(format!(
+        "  write_file: allowed\n    granted by group 'projects' at {}\n",
+        proj.display()
+    )))
+    .stdout(contains(format!(
+        "  remove_file: denied (checked on the parent, {})\n    group 'projects' at {} grants only",
+        proj.join("src").display(),
+        proj.display()
+    )))
+    .stdout(contains(
+        "  remove_dir: allowed, not handled by this profile",
+    ))
+    .stdout(contains("read_file:").not());
+
+    explain(&proj.join("escape"), "read_file")
+        .stdout(contains(format!(
+            "  read_file: denied\n    no rule covers {}",
+            outside.join("secret").display()
+        )))
+        .stdout(contains(format!(
+            "is below {} (group 'projects') by name, but resolves outside it",
+            proj.display()
+        )));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn protect_stops_new_entries_in_git_hooks() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join(format!("ai-sandbox-integ-protect-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("proj/.git/hooks")).unwrap();
+    std::fs::create_dir_all(dir.join("proj/src")).unwrap();
+    let proj = std::fs::canonicalize(dir.join("proj")).unwrap();
+    let cfg = dir.join("cfg.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            r#"version: 1
+profiles:
+  p:
+    protect: [vcs]
+    allow_reduced_dirs: true
+    access_roots:
+      system:
+        paths: [/usr, /bin, /lib, /lib64, /etc]
+        permissions: {{ read_file: true, read_dir: true, execute: true }}
+      projects:
+        paths: [{}]
+        permissions: {{ read_file: true, read_dir: true, write_file: true }}
+    command:
+      binary: /bin/sh
+      args: ["-c", "touch .git/hooks/new; ln -s ../../evil.sh .git/hooks/pre-commit; mkdir .git/hooks/d; touch src/ok"]
+      working_dir: {}
+"#,
+            proj.display(),
+            proj.display()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--require-landlock");
+    let out = cmd.output().unwrap();
+    if String::from_utf8_lossy(&out.stderr).contains("Landlock is required but not available") {
+        eprintln!("skipping: Landlock unavailable");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+    let hooks = proj.join(".git/hooks");
+    assert!(!hooks.join("new").exists());
+    assert!(std::fs::symlink_metadata(hooks.join("pre-commit")).is_err());
+    assert!(!hooks.join("d").exists());
+    assert!(proj.join("src/ok").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn print_ruleset_lists_nested_protections_and_reduced_dirs() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "ai-sandbox-integ-protect-nested-{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(dir.join("projects/app/.git")).unwrap();
+    std::fs::create_dir_all(dir.join("projects/org/lib/.git")).unwrap();
+    std::fs::create_dir_all(dir.join("elsewhere/wt-gitdir")).unwrap();
+    std::fs::create_dir_all(dir.join("projects/wt")).unwrap();
+    std::fs::write(
+        dir.join("projects/wt/.git"),
+        "gitdir: ../../elsewhere/wt-gitdir\n",
+    )
+    .unwrap();
+    let projects = std::fs::canonicalize(dir.join("projects")).unwrap();
+    let cfg = dir.join("cfg.yaml");
+    std::fs::write(
+        &cfg,
+        format!(
+            r#"version: 1
+profiles:
+  p:
+    protect: [vcs]
+    access_roots:
+      projects:
+        paths: [{}]
+        permissions: {{ read_file: true, read_dir: true, write_file: true }}
+    command: {{ binary: /bin/true }}
+"#,
+            projects.display()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config")
+        .arg(&cfg)
+        .arg("--profile")
+        .arg("p")
+        .arg("--print-ruleset");
+    cmd.assert()
+        .success()
+        .stdout(contains(format!(
+            "vcs: {}/app/.git (read-only)",
+            projects.display()
+        )))
+        .stdout(contains(format!(
+            "vcs: {}/org/lib/.git (read-only)",
+            projects.display()
+        )))
+        .stdout(contains(format!(
+            "vcs: {}/wt/.git (read-only)",
+            projects.display()
+        )))
+        .stdout(contains("wt-gitdir").not())
+        .stdout(contains("  reduced (warning: these directories lose"))
+        .stdout(contains(format!(
+            "    - {}: [\"WriteFile\", \"MakeReg\"",
+            projects.display()
+        )))
+        .stdout(contains(format!("    - {}/org/lib: [", projects.display())))
+        .stdout(contains(
+            "allow_reduced_dirs is not set: running this profile is refused",
+        ));
+
+    let mut cmd = bin_cmd();
+    cmd.arg("--config").arg(&cfg).arg("--profile").arg("p");
+    cmd.assert()
+        .failure()
+        .stderr(contains("set allow_reduced_dirs: true"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Read from a pty master until `needle` shows up; panics after `secs`.
+fn read_pty_until(master: &mut std::fs::File, needle: &str, secs: u64) -> String {
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(secs);
+    let mut seen = String::new();
+    while !seen.contains(needle) {
+        let left = deadline.saturating_duration_since(std::time::Instant::now());
+        assert!(
+            !left.is_zero(),
+            "timed out waiting for {:?}; got {:?}",
+            needle,
+            seen
+        );
+        let mut pfd = libc::pollfd {
+            fd: master.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: poll on one valid descriptor.
+        if unsafe { libc::poll(&mut pfd, 1, left.as_millis() as i32) } > 0 {
+            let mut buf = [0u8; 4096];
+            let n = master.read(&mut buf).unwrap_or(0);
+            seen.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+    }
+    seen
+}
+
+/// An interactive bash with job control on its own pty: its pid and the
+/// pty master. None without /bin/bash.
+fn spawn_job_control_shell() -> Option<(libc::pid_t, std::fs::File)> {
+    use std::os::unix::io::FromRawFd;
+    if !std::path::Path::new("/bin/bash").exists() {
+        eprintln!("skipping: /bin/bash not found");
+        return None;
+    }
+    let bash = std::ffi::CString::new("/bin/bash").unwrap();
+    let args: Vec<std::ffi::CString> = ["bash", "--norc", "--noprofile", "-i"]
+        .iter()
+        .map(|a| std::ffi::CString::new(*a).unwrap())
+        .collect();
+    let mut argv: Vec<*const libc::c_char> = args.iter().map(|a| a.as_ptr()).collect();
+    argv.push(std::ptr::null());
+
+    // An interactive shell with job control on its own terminal.
+    let mut master_fd = -1;
+    // SAFETY: the child only calls execv/_exit with data prepared before.
+    let pid = unsafe {
+        libc::forkpty(
+            &mut master_fd,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    };
+    assert!(pid >= 0, "forkpty failed");
+    if pid == 0 {
+        unsafe {
+            libc::execv(bash.as_ptr(), argv.as_ptr());
+            libc::_exit(127);
+        }
+    }
+    // SAFETY: forkpty returned a fresh descriptor we own.
+    Some((pid, unsafe { std::fs::File::from_raw_fd(master_fd) }))
+}
+
+#[test]
+fn ctrl_z_stops_launcher_and_fg_resumes_command() {
+    use std::io::Write;
+    let Some((pid, mut master)) = spawn_job_control_shell() else {
+        return;
+    };
+
+    // printf keeps the markers out of the terminal's echo of the line.
+    let line = format!(
+        "{} --log-level error --root / -- /bin/sh -c \"printf 'rea%s\\n' dy; sleep 2; printf 'fin%s\\n' ished\"\n",
+        assert_cmd::cargo::cargo_bin!("ai-sandbox-landlock").display()
+    );
+    master.write_all(line.as_bytes()).unwrap();
+    read_pty_until(&mut master, "ready", 10);
+    master.write_all(b"\x1a").unwrap();
+    read_pty_until(&mut master, "Stopped", 10);
+    master.write_all(b"fg\n").unwrap();
+    read_pty_until(&mut master, "finished", 10);
+    master.write_all(b"exit\n").unwrap();
+
+    let mut status = 0;
+    // SAFETY: waiting for the shell forked above.
+    unsafe { libc::waitpid(pid, &mut status, 0) };
+}
+
+#[test]
+fn background_command_reading_tty_resumes_with_fg() {
+    use std::io::Write;
+    let Some((pid, mut master)) = spawn_job_control_shell() else {
+        return;
+    };
+
+    // The command reads the terminal while the launcher is a background
+    // job, so it stops on SIGTTIN; the shell must see the job stopped, and
+    // `fg` must let the command read.
+    let line = format!(
+        "{} --log-level error --root / -- /bin/sh -c \"read x; printf 'go%s=%s\\n' t \\\"\\$x\\\"\" &\n",
+        assert_cmd::cargo::cargo_bin!("ai-sandbox-landlock").display()
+    );
+    master.write_all(line.as_bytes()).unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    master.write_all(b"\n").unwrap();
+    read_pty_until(&mut master, "Stopped", 10);
+    master.write_all(b"fg\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    master.write_all(b"hello\n").unwrap();
+    read_pty_until(&mut master, "got=hello", 10);
+    master.write_all(b"exit\n").unwrap();
+
+    let mut status = 0;
+    // SAFETY: waiting for the shell forked above.
+    unsafe { libc::waitpid(pid, &mut status, 0) };
+}